@@ -22,17 +22,25 @@
 //
 //! A client for use with internal and external modules.
 
-use std::net::TcpStream;
-use std::time::Duration;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::*;
 use url::Url;
-use crossbeam_channel::unbounded;
-use serde_json::Value;
+use crossbeam_channel::{unbounded, RecvTimeoutError};
+use parking_lot::Mutex;
+use serde_json::{Value, json};
 
 use crate::errors::{Error, Result};
 use crate::server::{CON_SENDER, REQ_SENDER, next_handler_id,
-                    HandlerId, ReqSender, RepReceiver};
-use crate::proto::{IncomingMsg, Msg};
+                    HandlerId, ReqSender, RepSender, RepReceiver};
+use crate::proto::{IncomingMsg, Msg, DISCOVERY_PORT};
 
+/// Default time to wait for the reply to a request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
 
 pub enum Client {
     Local(LocalClient),
@@ -40,21 +48,29 @@ pub enum Client {
 }
 
 impl Client {
+    /// Create a client for the given module address, using the default
+    /// timeout for requests.
     pub fn new(addr: &str) -> Result<Self> {
+        Self::with_timeout(addr, DEFAULT_TIMEOUT)
+    }
+
+    /// Create a client for the given module address.  Requests fail if there
+    /// is no reply within the timeout.
+    pub fn with_timeout(addr: &str, timeout: Duration) -> Result<Self> {
         let baseurl = Url::parse("local://").expect("valid URL");
         match Url::options().base_url(Some(&baseurl)).parse(addr) {
             Err(e) => panic!("{}", e),
             Ok(uri) => match uri.scheme() {
                 "local" => {
-                    let loc = LocalClient::new(&uri.path()[1..]).ok_or_else(
+                    let loc = LocalClient::new(&uri.path()[1..], timeout).ok_or_else(
                         || Error::comm_failed("no local server running"))?;
                     Ok(Client::Local(loc))
                 }
                 "secop" => {
-                    let host = uri.domain().unwrap_or("localhost");
+                    let host = uri.host_str().unwrap_or("localhost");
                     let port = uri.port().unwrap_or(10767);
                     let modname = uri.path()[1..].to_owned();
-                    RemoteClient::new(host, port, modname).map(Client::Remote)
+                    RemoteClient::new(host, port, modname, timeout).map(Client::Remote)
                 }
                 s => {
                     Err(Error::bad_value(format!("invalid URI scheme: {}", s)))
@@ -111,8 +127,7 @@ impl Drop for LocalClient {
 impl LocalClient {
     /// Return a new local client connecting to the given module.  None is
     /// returned if no local server is running.
-    pub fn new(modname: impl Into<String>) -> Option<Self> {
-        let timeout = Duration::from_secs(2); // TODO configurable
+    pub fn new(modname: impl Into<String>, timeout: Duration) -> Option<Self> {
         let hid = next_handler_id();
         let con_sender = CON_SENDER.lock().clone()?;
         let req_sender = REQ_SENDER.lock().clone()?;
@@ -140,6 +155,7 @@ impl LocalClient {
         let req = Msg::Read { module: self.modname.clone(), param: param.into() };
         match self.transact(req)? {
             Msg::Update { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for read: {}", msg)))
         }
     }
@@ -148,6 +164,7 @@ impl LocalClient {
         let req = Msg::Change { module: self.modname.clone(), param: param.into(), value };
         match self.transact(req)? {
             Msg::Changed { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for change: {}", msg)))
         }
     }
@@ -156,29 +173,147 @@ impl LocalClient {
         let req = Msg::Do { module: self.modname.clone(), command: cmd.into(), arg };
         match self.transact(req)? {
             Msg::Done { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for do: {}", msg)))
         }
     }
 }
 
 /// Client that accesses a module in some remote SEC node.
+///
+/// A background thread reads all incoming messages from the connection.
+/// Replies to the request currently in flight are handed back to `transact`,
+/// while events (updates for this or any other module) are put into a
+/// separate channel that can be obtained with `updates`.
 pub struct RemoteClient {
-    _conn: TcpStream,
+    conn: TcpStream,
     modname: String,
+    timeout: Duration,
+    /// Serializes requests; only one can be in flight at a time.
+    in_flight: Mutex<()>,
+    /// The request currently waiting for its reply, if any.
+    pending: Arc<Mutex<Option<Msg>>>,
+    connected: Arc<AtomicBool>,
+    rep_receiver: RepReceiver,
+    upd_receiver: RepReceiver,
+}
+
+impl Drop for RemoteClient {
+    fn drop(&mut self) {
+        // this also makes the reader thread quit
+        let _ = self.conn.shutdown(Shutdown::Both);
+    }
 }
 
 impl RemoteClient {
-    pub fn new(_host: &str, _port: u16, _modname: String) -> Result<Self> {
-        Err(Error::config("remote client connection not yet implemented"))
+    pub fn new(host: &str, port: u16, modname: String, timeout: Duration) -> Result<Self> {
+        let conn = TcpStream::connect((host, port))?;
+        conn.set_nodelay(true)?;
+        let reader = BufReader::new(conn.try_clone()?);
+        let pending = Arc::new(Mutex::new(None));
+        let connected = Arc::new(AtomicBool::new(true));
+        let (rep_sender, rep_receiver) = unbounded();
+        let (upd_sender, upd_receiver) = unbounded();
+        let thread_pending = Arc::clone(&pending);
+        let thread_connected = Arc::clone(&connected);
+        let thread_name = format!("{}:{}", host, port);
+        thread::spawn(move || RemoteClient::reader(&thread_name, reader, thread_pending,
+                                                   thread_connected, rep_sender, upd_sender));
+        Ok(Self { conn, modname, timeout, in_flight: Mutex::new(()), pending, connected,
+                  rep_receiver, upd_receiver })
+    }
+
+    /// Thread that reads and distributes incoming messages.
+    fn reader(name: &str, reader: BufReader<TcpStream>, pending: Arc<Mutex<Option<Msg>>>,
+              connected: Arc<AtomicBool>, rep_sender: RepSender, upd_sender: RepSender) {
+        mlzlog::set_thread_prefix(format!("[{}] ", name));
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    warn!("error in recv from remote node: {}", err);
+                    break;
+                }
+            };
+            let msg = match Msg::parse(line.trim_end_matches('\r').to_owned()) {
+                Ok(msg) => msg.1,
+                Err(msg) => {
+                    warn!("failed to parse line from remote node: {}", msg);
+                    continue;
+                }
+            };
+            // Since the reply to "read" is an "update" message, we can only
+            // decide by the request in flight whether it is an event or not.
+            let is_reply = match (&msg, &*pending.lock()) {
+                (Msg::Update { module, param, .. },
+                 Some(Msg::Read { module: req_module, param: req_param })) =>
+                    module == req_module && param == req_param,
+                (Msg::Update { .. }, _) => false,
+                _ => true,
+            };
+            let sender = if is_reply { &rep_sender } else { &upd_sender };
+            if sender.send(msg).is_err() {
+                break;
+            }
+        }
+        connected.store(false, Ordering::SeqCst);
+        info!("connection to remote node closed");
+    }
+
+    fn transact(&self, msg: Msg) -> Result<Msg> {
+        let _guard = self.in_flight.lock();
+        if !self.is_connected() {
+            return Err(Error::comm_failed("not connected to remote node"));
+        }
+        // discard any stray replies from previous (timed out) requests
+        self.rep_receiver.try_iter().count();
+        *self.pending.lock() = Some(msg.clone());
+        let result = writeln!(&self.conn, "{}", msg).map_err(Error::from).and_then(|_| {
+            match self.rep_receiver.recv_timeout(self.timeout) {
+                Ok(msg) => Ok(msg),
+                Err(RecvTimeoutError::Timeout) => Err(Error::comm_failed("remote node timed out")),
+                Err(RecvTimeoutError::Disconnected) =>
+                    Err(Error::comm_failed("connection to remote node lost")),
+            }
+        });
+        *self.pending.lock() = None;
+        result
+    }
+
+    /// Return true if the connection to the remote node is still open.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
     }
 
-    fn transact(&self, _msg: Msg) -> Result<Msg> {
-        unimplemented!()
+    /// Return the receiver for events sent by the remote node.  The channel
+    /// is disconnected when the connection to the remote node is lost.
+    pub fn updates(&self) -> &RepReceiver {
+        &self.upd_receiver
+    }
+
+    /// Return the descriptive data of the whole remote node.
+    pub fn describe(&self) -> Result<Value> {
+        match self.transact(Msg::Describe)? {
+            Msg::Describing { structure, .. } => Ok(structure),
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
+            msg => Err(Error::protocol(format!("invalid reply message for describe: {}", msg)))
+        }
+    }
+
+    /// Activate events for our module.  The initial updates arrive in the
+    /// `updates` channel.
+    pub fn activate(&self) -> Result<()> {
+        match self.transact(Msg::Activate { module: self.modname.clone() })? {
+            Msg::Active { .. } => Ok(()),
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
+            msg => Err(Error::protocol(format!("invalid reply message for activate: {}", msg)))
+        }
     }
 
     pub fn ping(&self) -> Result<()> {
         match self.transact(Msg::Ping { token: self.modname.clone() })? {
             Msg::Pong { ref token, .. } if token == &self.modname => Ok(()),
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for ping: {}", msg)))
         }
     }
@@ -187,6 +322,7 @@ impl RemoteClient {
         let req = Msg::Read { module: self.modname.clone(), param: param.into() };
         match self.transact(req)? {
             Msg::Update { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for read: {}", msg)))
         }
     }
@@ -195,6 +331,7 @@ impl RemoteClient {
         let req = Msg::Change { module: self.modname.clone(), param: param.into(), value };
         match self.transact(req)? {
             Msg::Changed { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for change: {}", msg)))
         }
     }
@@ -203,7 +340,73 @@ impl RemoteClient {
         let req = Msg::Do { module: self.modname.clone(), command: cmd.into(), arg };
         match self.transact(req)? {
             Msg::Done { data, .. } => Ok(data), // TODO extract value from report
+            Msg::ErrMsg { class, report } => Err(Error::from_wire(&class, &report)),
             msg => Err(Error::protocol(format!("invalid reply message for do: {}", msg)))
         }
     }
 }
+
+
+/// A SEC node that answered a discovery request.
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    /// Address of the node's TCP interface.
+    pub addr: SocketAddr,
+    pub equipment_id: String,
+    pub description: String,
+    pub firmware: String,
+}
+
+impl NodeInfo {
+    fn from_reply(from: SocketAddr, reply: &Value) -> Option<Self> {
+        if reply["SECoP"] != "node" {
+            return None;
+        }
+        let port = reply["port"].as_u64().filter(|&p| p > 0 && p <= 65535)?;
+        let string = |key: &str| reply[key].as_str().unwrap_or_default().to_owned();
+        Some(NodeInfo {
+            addr: SocketAddr::new(from.ip(), port as u16),
+            equipment_id: string("equipment_id"),
+            description: string("description"),
+            firmware: string("firmware"),
+        })
+    }
+}
+
+/// Find SEC nodes on the local network by broadcasting a discovery request,
+/// and collect the answers arriving within the given time.
+pub fn discover(timeout: Duration) -> Result<Vec<NodeInfo>> {
+    discover_at((Ipv4Addr::BROADCAST, DISCOVERY_PORT), timeout)
+}
+
+/// Send a discovery request to the given (broadcast or unicast) address,
+/// and collect the answers arriving within the given time.
+pub fn discover_at(target: impl ToSocketAddrs, timeout: Duration) -> Result<Vec<NodeInfo>> {
+    let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    sock.set_broadcast(true)?;
+    sock.send_to(json!({"SECoP": "discover"}).to_string().as_bytes(), target)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut nodes: Vec<NodeInfo> = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        sock.set_read_timeout(Some(deadline - now))?;
+        let (len, from) = match sock.recv_from(&mut buf) {
+            Ok(res) => res,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock |
+                                         std::io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let reply = serde_json::from_slice(&buf[..len]).ok();
+        match reply.and_then(|reply| NodeInfo::from_reply(from, &reply)) {
+            Some(node) if !nodes.iter().any(|n| n.addr == node.addr) => nodes.push(node),
+            Some(_) => (),
+            None => debug!("ignoring invalid discovery reply from {}", from),
+        }
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,118 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Time sources for timestamps, polling and simulations.
+//!
+//! Normally, the system clock is used.  For tests and simulations, a
+//! `VirtualClock` can be given to the server or the module internals instead,
+//! which only advances when told to, or runs faster than real time.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use mlzutil::time::localtime;
+use parking_lot::Mutex;
+
+/// How long to wait before looking at a hand-advanced clock again.
+const MANUAL_CHECK_INTERVAL: Duration = Duration::from_millis(1);
+
+pub trait Clock: Send + Sync {
+    /// Return the current time, in seconds since the Unix epoch.
+    fn now(&self) -> f64;
+
+    /// Return how long to wait, in real time, for the clock to advance by the
+    /// given number of seconds.  Callers must check `now()` afterwards, since
+    /// the clock may have advanced by less.
+    fn real_duration(&self, seconds: f64) -> Duration;
+
+    /// Block until the clock has advanced by the given number of seconds.
+    fn sleep(&self, seconds: f64) {
+        let until = self.now() + seconds;
+        loop {
+            let remaining = until - self.now();
+            if remaining <= 0. {
+                return;
+            }
+            thread::sleep(self.real_duration(remaining));
+        }
+    }
+}
+
+/// The real time of the system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        localtime()
+    }
+
+    fn real_duration(&self, seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds.max(0.))
+    }
+}
+
+/// A clock that is advanced by hand, and optionally also runs at a multiple
+/// of real time.
+#[derive(Debug)]
+pub struct VirtualClock {
+    /// The time at the last `advance`, and the real time of that moment.
+    base: Mutex<(f64, Instant)>,
+    /// Clock seconds per real second; zero if only advanced by hand.
+    speed: f64,
+}
+
+impl VirtualClock {
+    /// Create a clock starting at the given time, which only advances by
+    /// calls to `advance`.
+    pub fn new(start: f64) -> Self {
+        Self::with_speed(start, 0.)
+    }
+
+    /// Create a clock starting at the given time, which runs `speed` times
+    /// as fast as real time.
+    pub fn with_speed(start: f64, speed: f64) -> Self {
+        Self { base: Mutex::new((start, Instant::now())), speed: speed.max(0.) }
+    }
+
+    /// Advance the clock by the given number of seconds.
+    pub fn advance(&self, seconds: f64) {
+        let mut base = self.base.lock();
+        let now = Instant::now();
+        base.0 += (now - base.1).as_secs_f64() * self.speed + seconds;
+        base.1 = now;
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> f64 {
+        let base = self.base.lock();
+        base.0 + base.1.elapsed().as_secs_f64() * self.speed
+    }
+
+    fn real_duration(&self, seconds: f64) -> Duration {
+        if self.speed > 0. {
+            Duration::from_secs_f64(seconds.max(0.) / self.speed)
+        } else {
+            MANUAL_CHECK_INTERVAL
+        }
+    }
+}
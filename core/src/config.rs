@@ -23,12 +23,17 @@
 //! Configuration file handling.
 
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use serde_derive::{Serialize, Deserialize};
 use serde_json::Value;
 use toml;
 
-use crate::types::TypeInfo;
+use crate::errors::Error;
+use crate::history::MAX_HISTORY;
+use crate::module::{ClassInfo, ParamInfo};
+use crate::plugin::load_plugin;
+use crate::registry::Registry;
+use crate::types::{Enum, TypeInfo};
 
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -46,10 +51,22 @@ impl Default for Visibility {
 
 #[derive(Deserialize, Debug)]
 pub struct ServerConfig {
-    #[serde(skip)] // provided by us
+    /// If not given, the file name (without extension) is used.
+    #[serde(default)]
     pub equipment_id: String,
     pub description: String,
     pub modules: HashMap<String, ModuleConfig>,
+    /// Shared libraries to load additional module classes from, relative
+    /// to the directory of the config file.
+    #[serde(default)]
+    pub plugins: Vec<String>,
+    /// Directory for the node's state, such as persistent parameter values,
+    /// relative to the directory of the config file.
+    #[serde(default)]
+    pub datadir: Option<PathBuf>,
+    /// All other keys are additional node properties.
+    #[serde(flatten)]
+    pub properties: HashMap<String, Value>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -61,15 +78,66 @@ pub struct ModuleConfig {
     pub parameters: HashMap<String, Value>,
     #[serde(default)]
     pub visibility: Visibility,
+    #[serde(default)]
+    pub restart: RestartConfig,
+    /// Number of values to keep in the history of numeric parameters,
+    /// overriding the class's default.
+    #[serde(default)]
+    pub history: HashMap<String, usize>,
+    /// Members of enum parameters whose members are defined by the config,
+    /// given as a table of name = value or a list of names.
+    #[serde(default)]
+    pub enums: HashMap<String, Value>,
+    /// Names of the local modules this module uses, which must be started
+    /// before it.  Determined from the class's module reference parameters.
+    #[serde(skip)]
+    pub dependencies: Vec<String>,
+    /// All other keys are additional module properties, which override
+    /// those declared by the module class.
+    #[serde(flatten)]
+    pub properties: HashMap<String, Value>,
 }
 
 
-pub fn load_config(filename: impl AsRef<Path>) -> Result<ServerConfig, String> {
+/// Settings for automatically restarting a module after it failed.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestartConfig {
+    /// Delay before the first restart, in seconds.
+    pub delay: f64,
+    /// Factor by which the delay increases after each failure.
+    pub factor: f64,
+    /// Maximum delay between restarts, in seconds.
+    pub max_delay: f64,
+    /// Number of restarts after which the module is given up, if any.
+    pub max_restarts: Option<usize>,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self { delay: 1.0, factor: 2.0, max_delay: 60.0, max_restarts: None }
+    }
+}
+
+
+/// Load and check the config file.  Plugins given in the config are loaded
+/// into the registry, which is then used to check the module configurations.
+pub fn load_config(filename: impl AsRef<Path>, registry: &mut Registry)
+                   -> Result<ServerConfig, String> {
     let data = std::fs::read(&filename).map_err(|e| e.to_string())?;
     let mut obj: ServerConfig = toml::from_slice(&data).map_err(|e| e.to_string())?;
-    obj.equipment_id = filename.as_ref()
-                               .file_stem()
-                               .map_or("unknown".into(), |s| s.to_string_lossy().into_owned());
+    if obj.equipment_id.is_empty() {
+        obj.equipment_id = filename.as_ref()
+                                   .file_stem()
+                                   .map_or("unknown".into(), |s| s.to_string_lossy().into_owned());
+    } else if obj.equipment_id.contains(char::is_whitespace) {
+        return Err(format!("equipment_id {:?} must not contain whitespace", obj.equipment_id));
+    }
+
+    // Check the additional node properties.
+    for (key, value) in &obj.properties {
+        check_node_property(key, value, &obj.modules)?;
+    }
 
     // Check module names and groups for lowercase-uniqueness.
     let mut lc_names = HashSet::new();
@@ -86,11 +154,226 @@ pub fn load_config(filename: impl AsRef<Path>) -> Result<ServerConfig, String> {
         }
     }
 
-    // TODO: check presence of mandatory params
+    // Check the additional module properties and restart settings.
+    for (name, modcfg) in &obj.modules {
+        let restart = &modcfg.restart;
+        if !(restart.delay > 0.0 && restart.factor >= 1.0 && restart.max_delay >= restart.delay) {
+            return Err(format!("module {}: restart delays must be positive and factor \
+                                must be at least 1", name));
+        }
+        for (key, value) in &modcfg.properties {
+            check_module_property(key, value).map_err(|e| format!("module {}: {}", name, e))?;
+        }
+    }
+
+    // Load plugins, which can provide additional module classes.  Make sure
+    // the path is not looked up in the library search path.
+    let basedir = match filename.as_ref().parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    for plugin in &obj.plugins {
+        load_plugin(registry, &basedir.join(plugin)).map_err(
+            |e| format!("could not load plugin {}: {}", plugin, e))?;
+    }
+    if let Some(datadir) = obj.datadir.take() {
+        let datadir = basedir.join(datadir);
+        if !datadir.is_dir() {
+            return Err(format!("data directory {} does not exist", datadir.display()));
+        }
+        obj.datadir = Some(datadir);
+    }
+
+    // Check module classes and parameters, reporting all problems at once.
+    let mut errors = Vec::new();
+    for (name, modcfg) in &obj.modules {
+        match registry.class_info(&modcfg.class) {
+            None => errors.push(format!("module {}: no such module class: {}", name, modcfg.class)),
+            Some(info) => errors.extend(check_module_params(&info, name, modcfg, &obj.modules)
+                                        .into_iter().map(|e| format!("module {}: {}", name, e))),
+        }
+    }
+    if !errors.is_empty() {
+        errors.sort();
+        return Err(errors.join("\n"));
+    }
+
+    // Determine the dependencies between modules, which must not be cyclic.
+    for modcfg in obj.modules.values_mut() {
+        if let Some(info) = registry.class_info(&modcfg.class) {
+            let deps = info.params.iter()
+                                  .filter(|p| p.module_ref)
+                                  .filter_map(|p| local_module_ref(modcfg.parameters.get(p.name)?))
+                                  .map(Into::into)
+                                  .collect();
+            modcfg.dependencies = deps;
+        }
+    }
+    startup_order(&obj.modules)?;
 
     Ok(obj)
 }
 
+/// Return the module name if the value of a module reference parameter
+/// refers to a module of this node.  Modules on other nodes are given as
+/// `secop://` or `tcp://` URIs, local modules by name or as `local://` URIs.
+fn local_module_ref(value: &Value) -> Option<&str> {
+    let modref = value.as_str()?;
+    match modref.split_once("://") {
+        None => Some(modref),
+        Some(("secop", _)) | Some(("tcp", _)) => None,
+        Some(("local", name)) => Some(name.trim_start_matches('/')),
+        // other schemes are reported as unknown modules
+        Some(_) => Some(modref),
+    }
+}
+
+/// Determine an order to start the modules in, so that each module is started
+/// after its dependencies.  Fails if there is a dependency cycle.
+pub fn startup_order(modules: &HashMap<String, ModuleConfig>) -> Result<Vec<String>, String> {
+    fn visit<'a>(name: &'a str, modules: &'a HashMap<String, ModuleConfig>,
+                 path: &mut Vec<&'a str>, order: &mut Vec<String>) -> Result<(), String> {
+        if order.iter().any(|n| n == name) {
+            return Ok(());
+        }
+        if let Some(pos) = path.iter().position(|&n| n == name) {
+            let mut cycle = path[pos..].to_vec();
+            cycle.push(name);
+            return Err(format!("dependency cycle between modules: {}", cycle.join(" -> ")));
+        }
+        path.push(name);
+        for dep in modules.get(name).map_or(&[][..], |cfg| &cfg.dependencies) {
+            visit(dep, modules, path, order)?;
+        }
+        path.pop();
+        order.push(name.into());
+        Ok(())
+    }
+
+    // sort to get a reproducible order
+    let mut names = modules.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut order = Vec::with_capacity(names.len());
+    for name in names {
+        visit(name, modules, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Check the parameters given in a module's config against the class.
+fn check_module_params(info: &ClassInfo, name: &str, modcfg: &ModuleConfig,
+                       modules: &HashMap<String, ModuleConfig>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (param, value) in &modcfg.parameters {
+        match info.param(param) {
+            None => errors.push(format!("class {} has no parameter {}", modcfg.class, param)),
+            Some(pinfo) if pinfo.readonly && !pinfo.swonly =>
+                errors.push(format!("parameter {} is read from hardware and cannot be \
+                                     configured", param)),
+            Some(pinfo) => if let Err(e) = check_param_value(pinfo, modcfg, value) {
+                errors.push(format!("invalid value for parameter {}: {}", param, e));
+            } else if pinfo.module_ref {
+                if let Some(modref) = local_module_ref(value) {
+                    if modref == name {
+                        errors.push(format!("parameter {} refers to the module itself", param));
+                    } else if !modules.contains_key(modref) {
+                        errors.push(format!("parameter {} refers to unknown module {}",
+                                            param, modref));
+                    }
+                }
+            }
+        }
+    }
+    for pinfo in &info.params {
+        if pinfo.mandatory && !modcfg.parameters.contains_key(pinfo.name) {
+            errors.push(format!("mandatory parameter {} is missing", pinfo.name));
+        }
+    }
+    for (param, members) in &modcfg.enums {
+        match info.param(param) {
+            None => errors.push(format!("class {} has no parameter {}", modcfg.class, param)),
+            Some(pinfo) if !pinfo.config_enum =>
+                errors.push(format!("parameter {} does not take enum members from the config",
+                                    param)),
+            _ => if let Err(e) = Enum::from_config(members) {
+                errors.push(format!("invalid enum members for parameter {}: {}", param, e));
+            }
+        }
+    }
+    for (param, &size) in &modcfg.history {
+        match info.param(param) {
+            None => errors.push(format!("class {} has no parameter {}", modcfg.class, param)),
+            Some(pinfo) if !matches!(pinfo.datainfo["type"].as_str(),
+                                     Some("double" | "int" | "scaled")) =>
+                errors.push(format!("parameter {} does not support a history", param)),
+            _ if size > MAX_HISTORY =>
+                errors.push(format!("history of parameter {} cannot be longer than {}",
+                                    param, MAX_HISTORY)),
+            _ => ()
+        }
+    }
+    errors
+}
+
+/// Check a parameter value given in the config.  For enums with members from
+/// the config, the value is checked against the configured members.
+fn check_param_value(pinfo: &ParamInfo, modcfg: &ModuleConfig, value: &Value)
+                     -> Result<(), Error> {
+    match modcfg.enums.get(pinfo.name).filter(|_| pinfo.config_enum) {
+        // invalid members are reported separately
+        Some(members) => match Enum::from_config(members) {
+            Ok(info) => info.from_json(value).map(|_| ()),
+            Err(_) => Ok(()),
+        },
+        None => (pinfo.check)(value),
+    }
+}
+
+
+/// Check a node property given in the config for validity.
+///
+/// Apart from the predefined properties, custom ones are allowed if their
+/// name starts with an underscore (e.g. `_contact`), and can have any value.
+fn check_node_property(key: &str, value: &Value, modules: &HashMap<String, ModuleConfig>)
+                       -> Result<(), String> {
+    let valid = match key {
+        "implementor" | "interface" => value.is_string(),
+        "timeout" => value.as_f64().map_or(false, |v| v > 0.0),
+        "order" => value.as_array().map_or(false, |names| names.iter().all(
+            |name| name.as_str().map_or(false, |name| modules.contains_key(name)))),
+        "firmware" => return Err("node property firmware cannot be configured".into()),
+        _ if key.starts_with('_') => true,
+        _ => return Err(format!("unknown node property {} (custom properties must \
+                                 start with an underscore)", key)),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid value for node property {}: {}", key, value))
+    }
+}
+
+/// Check a module property given in the config for validity.
+///
+/// As for the node, custom properties must start with an underscore.
+fn check_module_property(key: &str, value: &Value) -> Result<(), String> {
+    let is_str_list = |v: &Value| v.as_array().map_or(false, |a| a.iter().all(Value::is_string));
+    let valid = match key {
+        "implementation" => value.is_string(),
+        "interface_classes" | "features" => is_str_list(value),
+        "meaning" => value.as_array().map_or(false, |a| a.len() == 2 && a[0].is_string() &&
+                                              a[1].is_i64()),
+        _ if key.starts_with('_') => true,
+        _ => return Err(format!("unknown module property or key {} (custom properties \
+                                 must start with an underscore)", key)),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid value for module property {}: {}", key, value))
+    }
+}
+
 
 // TODO: check if this is necessary vs. initialized parameters
 impl ModuleConfig {
@@ -98,3 +381,54 @@ impl ModuleConfig {
         self.parameters.get(param).and_then(|v| td.from_json(v).ok())
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn modules(deps: &[(&str, &[&str])]) -> HashMap<String, ModuleConfig> {
+        deps.iter().map(|(name, deps)| {
+            let mut cfg: ModuleConfig = serde_json::from_value(json!({
+                "class": "Test", "description": "test"
+            })).unwrap();
+            cfg.dependencies = deps.iter().map(|&dep| dep.into()).collect();
+            (name.to_string(), cfg)
+        }).collect()
+    }
+
+    #[test]
+    fn dependencies_first() {
+        let order = startup_order(&modules(&[("a", &["c"]), ("b", &[]), ("c", &["b"]),
+                                             ("d", &["a", "b"])])).unwrap();
+        assert_eq!(order, ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn sorted_without_dependencies() {
+        let order = startup_order(&modules(&[("z", &[]), ("m", &[]), ("a", &[]),
+                                             ("x", &["y"]), ("y", &[])])).unwrap();
+        assert_eq!(order, ["a", "m", "y", "x", "z"]);
+    }
+
+    #[test]
+    fn cycle() {
+        let err = startup_order(&modules(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]),
+                                           ("d", &[])])).unwrap_err();
+        assert_eq!(err, "dependency cycle between modules: a -> b -> c -> a");
+        let err = startup_order(&modules(&[("a", &["a"])])).unwrap_err();
+        assert_eq!(err, "dependency cycle between modules: a -> a");
+    }
+
+    #[test]
+    fn local_refs() {
+        assert_eq!(local_module_ref(&json!("temp")), Some("temp"));
+        assert_eq!(local_module_ref(&json!("local://temp")), Some("temp"));
+        assert_eq!(local_module_ref(&json!("local:///temp")), Some("temp"));
+        assert_eq!(local_module_ref(&json!("secop://host:10767/temp")), None);
+        assert_eq!(local_module_ref(&json!("tcp://host/temp")), None);
+        assert_eq!(local_module_ref(&json!("http://host/temp")), Some("http://host/temp"));
+        assert_eq!(local_module_ref(&json!(1)), None);
+    }
+}
@@ -23,7 +23,7 @@
 //! Enumeration of possible SECoP errors.
 
 use std::{error, fmt, result};
-use serde_json::json;
+use serde_json::{Value, json};
 
 use crate::proto::Msg;
 
@@ -101,6 +101,28 @@ impl Error {
         }
     }
 
+    /// Reconstruct an error from an error reply received from another node.
+    pub fn from_wire(class: &str, report: &Value) -> Self {
+        use self::ErrorKind::*;
+        let kind = match class {
+            "ProtocolError" => Protocol,
+            "NoSuchModule" => NoSuchModule,
+            "NoSuchParameter" => NoSuchParameter,
+            "NoSuchCommand" => NoSuchCommand,
+            "CommandFailed" => CommandFailed,
+            "CommandRunning" => CommandRunning,
+            "ReadOnly" => ReadOnly,
+            "BadValue" => BadValue,
+            "CommunicationFailed" => CommunicationFailed,
+            "IsBusy" => IsBusy,
+            "IsError" => IsError,
+            "Disabled" => Disabled,
+            _ => Programming,
+        };
+        let message = report[1].as_str().unwrap_or_default().into();
+        Self { kind, message }
+    }
+
     // Quick construction.
 
     pub fn config(msg: impl Into<String>) -> Self {
@@ -0,0 +1,137 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Bounded history of parameter values, with statistics.
+//!
+//! Parameters declared with `#[param(history="N")]`, or configured with
+//! `history.<name> = N`, keep their last N values (numeric parameters only),
+//! which module code can use through `ModParam::history()`, and clients
+//! through the `get_history` command.
+
+use std::collections::VecDeque;
+use secop_derive::TypeInfo;
+
+use crate::types::{ArrayOf, Double, Str};
+
+/// The maximum number of values that can be kept per parameter.
+pub const MAX_HISTORY: usize = 100_000;
+
+/// Ring buffer of (time, value) pairs of a parameter.
+#[derive(Debug, Clone)]
+pub struct History {
+    size: usize,
+    entries: VecDeque<(f64, f64)>,
+}
+
+/// Statistics over the values in a time window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl History {
+    pub fn new(size: usize) -> Self {
+        let size = size.min(MAX_HISTORY);
+        Self { size, entries: VecDeque::with_capacity(size) }
+    }
+
+    /// Add a new value, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, time: f64, value: f64) {
+        if self.entries.len() == self.size {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((time, value));
+    }
+
+    /// Return the values since the given time.  This includes the last value
+    /// from before that time, which was still current at that time.
+    pub fn since(&self, time: f64) -> impl Iterator<Item=(f64, f64)> + '_ {
+        let newer = self.entries.iter().rposition(|&(t, _)| t <= time).unwrap_or(0);
+        self.entries.range(newer..).copied()
+    }
+
+    /// Return the time of the oldest value kept, if any.
+    pub fn start(&self) -> Option<f64> {
+        self.entries.front().map(|&(t, _)| t)
+    }
+
+    /// Calculate statistics over the values since the given time.
+    pub fn stats(&self, time: f64) -> Option<HistoryStats> {
+        let (mut count, mut min, mut max, mut sum) = (0, f64::INFINITY, f64::NEG_INFINITY, 0.);
+        for (_, v) in self.since(time) {
+            count += 1;
+            min = min.min(v);
+            max = max.max(v);
+            sum += v;
+        }
+        if count == 0 {
+            return None;
+        }
+        let mean = sum / count as f64;
+        // A second pass over the deviations from the mean stays accurate for
+        // values with a large offset and a small spread.
+        let sumsq: f64 = self.since(time).map(|(_, v)| (v - mean) * (v - mean)).sum();
+        let stddev = (sumsq / count as f64).sqrt();
+        Some(HistoryStats { count, min, max, mean, stddev })
+    }
+
+    /// Answer a `get_history` query for the given window, ending at `now`.
+    pub fn query(&self, window: Option<f64>, now: f64) -> HistoryData {
+        let since = window.map_or(f64::NEG_INFINITY, |w| now - w);
+        let (t, values) = self.since(since).unzip();
+        let stats = self.stats(since);
+        HistoryData { t, values,
+                      min: stats.map(|s| s.min), max: stats.map(|s| s.max),
+                      mean: stats.map(|s| s.mean), stddev: stats.map(|s| s.stddev) }
+    }
+}
+
+/// Argument of the `get_history` command.
+#[derive(TypeInfo, Clone, PartialEq, Default)]
+pub struct HistoryQuery {
+    #[datainfo="Str()"]
+    pub param: String,
+    /// Time window in seconds; if not given, all values are returned.
+    #[datainfo="Double(min=0.0)"]
+    pub window: Option<f64>,
+}
+
+/// Result of the `get_history` command.
+#[derive(TypeInfo, Clone, PartialEq, Default)]
+pub struct HistoryData {
+    #[datainfo="[Double(); 0..=MAX_HISTORY]"]
+    pub t: Vec<f64>,
+    #[datainfo="[Double(); 0..=MAX_HISTORY]"]
+    pub values: Vec<f64>,
+    #[datainfo="Double()"]
+    pub min: Option<f64>,
+    #[datainfo="Double()"]
+    pub max: Option<f64>,
+    #[datainfo="Double()"]
+    pub mean: Option<f64>,
+    #[datainfo="Double()"]
+    pub stddev: Option<f64>,
+}
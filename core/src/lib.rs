@@ -28,6 +28,12 @@ pub mod server;
 pub mod client;
 pub mod config;
 pub mod module;
+pub mod clock;
+pub mod history;
+pub mod stabilize;
+pub mod registry;
+pub mod plugin;
+pub mod state;
 pub mod errors;
 
 // Hack to allow the derives to derive stuff in this crate.
@@ -37,14 +43,26 @@ mod secop_core {
     pub use crate::types;
 }
 
+/// Attribute macros for declaring a module on its handler impl block, to be
+/// used as `#[secop::module]`, `#[secop::param]` etc.
+pub mod secop {
+    pub use secop_derive::{secop_module as module, secop_param as param,
+                           secop_command as command, secop_property as property,
+                           secop_stabilizer as stabilizer};
+}
+
 /// Re-exports mostly everything needed for writing modules.
 pub mod prelude {
+    pub use crate::secop;
     pub use crate::errors::{Error, ErrorKind, Result};
-    pub use crate::module::{ModInternals, ModuleBase, Module};
+    pub use crate::module::{ModInternals, ModuleBase, Module, Qualifiers};
     pub use crate::config::{ServerConfig, ModuleConfig};
     pub use crate::client::Client;
+    pub use crate::registry::Registry;
+    pub use crate::clock::Clock;
     pub use crate::types::{TypeInfo, Null, Bool, Double, Int, Blob,
-                           Str, ArrayOf, Tuple2, Tuple3, Tuple4,
-                           Tuple5, Tuple6, Enum, StatusConst,
-                           StatusType, Status};
+                           Str, ArrayOf, Tuple1, Tuple2, Tuple3, Tuple4,
+                           Tuple5, Tuple6, Tuple7, Tuple8, Tuple9, Tuple10,
+                           Tuple11, Tuple12, Matrix, MatrixData, Enum, AnyType,
+                           Struct, Serde, SerdeSchema, StatusConst, StatusType, Status};
 }
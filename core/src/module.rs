@@ -24,18 +24,24 @@
 
 use std::fmt;
 use std::ops::Deref;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::cmp;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use log::*;
 use serde_json::{Value, json};
 use derive_new::new;
-use mlzutil::time::localtime;
-use crossbeam_channel::{tick, Receiver, select};
+use crossbeam_channel::{Sender, select};
 
+use crate::clock::{Clock, SystemClock};
 use crate::config::{ModuleConfig, Visibility};
 use crate::errors::Error;
+use crate::history::History;
+use crate::stabilize::Stabilizer;
 use crate::proto::Msg;
 use crate::server::{ReqReceiver, ModRepSender};
-use crate::types::TypeInfo;
+use crate::state::StateStore;
+use crate::types::{TypeInfo, StatusType};
 
 /// Data that every module requires.
 #[derive(new, Clone)]
@@ -44,7 +50,25 @@ pub struct ModInternals {
     config: ModuleConfig,
     req_receiver: ReqReceiver,
     rep_sender: ModRepSender,
-    poll_tickers: (Receiver<Instant>, Receiver<Instant>),
+    /// Signals the server that the module has completed setup.
+    ready_sender: Sender<()>,
+    /// Storage for values of persistent parameters.
+    state: Arc<StateStore>,
+    /// Poll interval in seconds for parameters without their own interval.
+    #[new(value = "1.0")]
+    pollinterval: f64,
+    /// Source of timestamps and poll times.
+    #[new(value = "Arc::new(SystemClock)")]
+    clock: Arc<dyn Clock>,
+    /// Detects when the module's value has reached its target.
+    #[new(default)]
+    stabilizer: Stabilizer,
+    /// Set once the module has completed setup for the first time.
+    #[new(default)]
+    setup_done: Arc<AtomicBool>,
+    /// How often the module has been restarted after a failure.
+    #[new(default)]
+    restarts: usize,
 }
 
 impl ModInternals {
@@ -60,14 +84,208 @@ impl ModInternals {
     pub fn req_receiver(&self) -> &ReqReceiver {
         &self.req_receiver
     }
+    pub fn rep_sender(&self) -> &ModRepSender {
+        &self.rep_sender
+    }
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+    pub fn stabilizer(&self) -> &Stabilizer {
+        &self.stabilizer
+    }
+    pub fn stabilizer_mut(&mut self) -> &mut Stabilizer {
+        &mut self.stabilizer
+    }
+    /// Use the given clock instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Get the stored value of a persistent parameter.
+    pub fn persisted(&self, param: &str) -> Option<Value> {
+        self.state.get(&self.name, param)
+    }
+    /// Store a new value of a persistent parameter.
+    pub fn persist(&self, param: &str, value: Value) {
+        self.state.set(&self.name, param, value)
+    }
+    pub(crate) fn set_restarts(&mut self, restarts: usize) {
+        self.restarts = restarts;
+    }
+    pub(crate) fn setup_done(&self) -> bool {
+        self.setup_done.load(Ordering::SeqCst)
+    }
+    /// Tell the server that modules depending on us can be started.  After
+    /// the first time, nobody is waiting anymore.
+    pub(crate) fn notify_ready(&self) {
+        let _ = self.ready_sender.try_send(());
+    }
+}
+
+/// Description of a module whose accessibles are not (yet) known, with only
+/// the status parameter.
+pub fn status_only_description(config: &ModuleConfig) -> Value {
+    json!({
+        "description": config.description,
+        "interface_classes": [],
+        "features": [],
+        "visibility": config.visibility,
+        "group": config.group,
+        "accessibles": {
+            "status": {
+                "description": "status",
+                "datainfo": serde_json::to_value(&StatusType).unwrap(),
+                "readonly": true,
+            }
+        }
+    })
+}
+
+/// Static information about a module class, used to check configurations
+/// before any module is started.
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    /// Name of the implementing type.
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub params: Vec<ParamInfo>,
+    pub commands: Vec<CommandInfo>,
+}
+
+/// Static information about a parameter of a module class.
+#[derive(Clone)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub datainfo: Value,
+    pub readonly: bool,
+    pub swonly: bool,
+    pub mandatory: bool,
+    /// The default value, as given in the code.
+    pub default: Option<&'static str>,
+    /// If true, the value is the name of another module used by this one.
+    pub module_ref: bool,
+    /// Number of values to keep in the history, or zero.
+    pub history: usize,
+    /// If true, the parameter is an `Enum` whose members can be given in the
+    /// config.
+    pub config_enum: bool,
+    /// Checks a value given in the config against the datainfo.
+    pub check: fn(&Value) -> Result<(), Error>,
+}
+
+// Deriving Debug does not work for the higher-ranked function pointer on all
+// supported compilers, so leave out `check`.
+impl fmt::Debug for ParamInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParamInfo")
+         .field("name", &self.name)
+         .field("doc", &self.doc)
+         .field("datainfo", &self.datainfo)
+         .field("readonly", &self.readonly)
+         .field("swonly", &self.swonly)
+         .field("mandatory", &self.mandatory)
+         .field("default", &self.default)
+         .field("module_ref", &self.module_ref)
+         .field("history", &self.history)
+         .field("config_enum", &self.config_enum)
+         .finish()
+    }
+}
+
+/// Static information about a command of a module class.
+#[derive(Debug, Clone)]
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub argument: Value,
+    pub result: Value,
+}
+
+impl ClassInfo {
+    pub fn param(&self, name: &str) -> Option<&ParamInfo> {
+        self.params.iter().find(|p| p.name == name)
+    }
+}
+
+/// Poll intervals of a parameter, in seconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PollInterval {
+    /// If not given, the module's `pollinterval` is used.
+    pub normal: Option<f64>,
+    /// Interval while the module is busy.  If not given, it is 1/5 of the
+    /// normal interval; if zero, the normal interval is kept.
+    pub busy: Option<f64>,
+}
+
+impl PollInterval {
+    fn get(&self, pollinterval: f64, busy: bool) -> f64 {
+        let normal = self.normal.unwrap_or(pollinterval);
+        match self.busy {
+            _ if !busy => normal,
+            None => normal / 5.,
+            Some(busy) if busy > 0. => busy,
+            Some(_) => normal,
+        }
+    }
+}
+
+/// Decides which parameter is polled next.  Each parameter is polled at its
+/// own interval, and only one at a time, so that requests are handled in
+/// between.
+struct PollScheduler {
+    /// Parameters with their intervals and the time of the last poll.
+    params: Vec<(&'static str, PollInterval, f64)>,
+}
+
+impl PollScheduler {
+    fn new(params: Vec<(&'static str, PollInterval)>, pollinterval: f64, now: f64) -> Self {
+        // Spread the first polls over the interval, so that the parameters
+        // are not all read at once.
+        let n = params.len() as f64;
+        let params = params.into_iter().zip(1..).map(|((name, interval), i)| {
+            let offset = interval.get(pollinterval, false) * (n - i as f64) / n;
+            (name, interval, now - offset)
+        }).collect();
+        Self { params }
+    }
+
+    /// Return the index of the parameter to poll next, and when it is due.
+    fn next(&self, pollinterval: f64, busy: bool) -> Option<(usize, f64)> {
+        self.params.iter().enumerate().map(|(i, (_, interval, last))| {
+            (i, last + interval.get(pollinterval, busy))
+        }).min_by(|(_, due1), (_, due2)| due1.partial_cmp(due2).unwrap_or(cmp::Ordering::Equal))
+    }
+
+    /// Mark a parameter as polled at the given time, and return its name.
+    fn polled(&mut self, index: usize, now: f64) -> &'static str {
+        self.params[index].2 = now;
+        self.params[index].0
+    }
+}
+
+/// Qualifiers that a read method can report along with the value.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Qualifiers {
+    /// Time at which the value was determined, if known from the hardware.
+    /// Otherwise, the time of reading is used.
+    pub timestamp: Option<f64>,
+    /// Error (uncertainty) of the value, in the same unit.
+    pub error: Option<f64>,
 }
 
 /// Data bag for a single parameter value.
 pub struct ModParam<I: TypeInfo> {
     data: I::Repr,
     time: f64,
+    error: Option<f64>,
     /// TypeInfo for the parameter
     pub info: I,
+    /// Past values, if enabled
+    history: Option<History>,
 }
 
 impl<I: TypeInfo> Deref for ModParam<I> {
@@ -93,32 +311,80 @@ impl<I: TypeInfo> ModParam<I>
 where I::Repr: PartialEq + Clone + Default
 {
     pub fn new(info: I) -> Self {
-        Self { data: Default::default(), time: localtime(), info }
+        Self { data: info.default_value(), time: 0., error: None, info, history: None }
+    }
+
+    /// Keep the given number of past values (zero to disable).
+    pub fn with_history(mut self, size: usize) -> Self {
+        self.set_history_size(size);
+        self
     }
 
-    pub fn set(&mut self, value: I::Repr) {
-        self.time = localtime();
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history = if size > 0 { Some(History::new(size)) } else { None };
+    }
+
+    /// Return the past values of the parameter, if enabled.
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Add a value to the history, if enabled and numeric.
+    fn record(&mut self, value: &Value) {
+        if let (Some(history), Some(value)) = (&mut self.history, value.as_f64()) {
+            history.push(self.time, value);
+        }
+    }
+
+    pub fn set(&mut self, value: I::Repr, time: f64) {
+        self.time = time;
+        self.error = None;
+        if self.history.is_some() {
+            if let Ok(json) = self.info.to_json(value.clone()) {
+                self.record(&json);
+            }
+        }
         self.data = value;
     }
 
     /// Gets a newly determined value for this parameter, which is then cached,
     /// possibly an update message is sent, and the value is returned JSONified
     /// for sending in a reply.
-    pub fn update(&mut self, value: I::Repr) -> Result<(Value, f64, bool), Error> {
-        self.time = localtime();
-        let is_update = if value != self.data {
+    pub fn update(&mut self, value: I::Repr, time: f64) -> Result<(Value, f64, bool), Error> {
+        self.update_qualified(value, Qualifiers::default(), time)
+    }
+
+    /// Like `update`, but with qualifiers reported by the hardware.  The
+    /// timestamp, if given, replaces the time of reading.  A change of the
+    /// error also counts as an update.
+    pub fn update_qualified(&mut self, value: I::Repr, qualifiers: Qualifiers, time: f64)
+                            -> Result<(Value, f64, bool), Error> {
+        self.time = qualifiers.timestamp.unwrap_or(time);
+        let is_update = if value != self.data || qualifiers.error != self.error {
             self.data = value.clone();
+            self.error = qualifiers.error;
             true
         } else {
             false
         };
-        Ok((self.info.to_json(value)?, self.time, is_update))
+        let json = self.info.to_json(value)?;
+        self.record(&json);
+        Ok((json, self.time, is_update))
     }
 
     pub fn time(&self) -> f64 {
         self.time
     }
 
+    /// Return the qualifiers of the current value, as sent in data reports.
+    pub fn qualifiers(&self) -> Value {
+        let mut qualifiers = json!({"t": self.time});
+        if let Some(error) = self.error {
+            qualifiers["e"] = json!(error);
+        }
+        qualifiers
+    }
+
     pub fn to_json(&self) -> Result<Value, Error> {
         self.info.to_json(self.data.clone())
     }
@@ -133,6 +399,8 @@ pub trait Module : ModuleBase {
 
 /// Part of the Module trait that is implemented by the derive macro.
 pub trait ModuleBase {
+    /// Return the static information about this module class.
+    fn class_info() -> ClassInfo where Self: Sized;
     /// Return the descriptive data for this module (a JSON object).
     fn describe(&self) -> Value;
     /// Execute a command.
@@ -148,11 +416,17 @@ pub trait ModuleBase {
     /// activation of the module.
     fn activate_updates(&mut self) -> Vec<Msg>;
 
-    /// Poll parameters.  If device is busy, parameters that participate in
-    /// busy-poll are not polled.
-    fn poll_normal(&mut self, n: usize);
-    /// Poll parameters that participate in busy-poll if device status is busy.
-    fn poll_busy(&mut self, n: usize);
+    /// Return the parameters to poll, with their poll intervals.
+    fn polled_params() -> Vec<(&'static str, PollInterval)> where Self: Sized;
+    /// Return true if the module is busy, so that busy poll intervals apply.
+    fn is_busy(&self) -> bool;
+    /// Poll a single parameter.  Failures are logged, the parameter keeps
+    /// its last value.
+    fn poll(&mut self, param: &str) {
+        if let Err(e) = self.read(param) {
+            warn!("polling parameter {} failed: {}", param, e);
+        }
+    }
 
     /// Return a reference to the module internals.  Even though we require
     /// the internals to be a member with a fixed name, the member is not
@@ -169,16 +443,43 @@ pub trait ModuleBase {
     /// This is quite complex since we have multiple sources (defaults from
     /// code, config file, hardware) and multiple ways of using them (depending
     /// on whether the parameter is writable at runtime).
+    ///
+    /// The first available of these sources is used:
+    ///
+    /// * the last value, for persistent parameters (ignored if not valid)
+    /// * the value from the config file
+    /// * the default value from the code
+    /// * the value read from hardware (not for software-only parameters)
+    ///
+    /// Readonly hardware parameters are always read from hardware.
+    #[allow(clippy::too_many_arguments)]
     fn init_parameter<I>(
         &mut self, param: &str, cached: impl Fn(&mut Self) -> &mut ModParam<I>,
         update: impl Fn(&mut Self, I::Repr) -> Result<(), Error>,
-        swonly: bool, readonly: bool, default: Option<impl Fn() -> I::Repr>
+        swonly: bool, readonly: bool, persistent: bool, default: Option<impl Fn() -> I::Repr>
     ) -> Result<(), Error>
         where I: TypeInfo, I::Repr: Clone + PartialEq + Default
     {
         let datainfo = cached(self).info.clone();
+        if let Some(&size) = self.config().history.get(param) {
+            cached(self).set_history_size(size);
+        }
+        let persisted = if persistent {
+            self.internals().persisted(param).filter(|val| match datainfo.from_json(val) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("ignoring persisted value for param {}: {}", param, e);
+                    false
+                }
+            })
+        } else {
+            None
+        };
         if swonly {
-            let value = if let Some(def) = default {
+            let value = if let Some(val) = persisted {
+                debug!("initializing value for param {} (from persisted state)", param);
+                datainfo.from_json(&val)?
+            } else if let Some(def) = default {
                 if let Some(val) = self.config().parameters.get(param) {
                     debug!("initializing value for param {} (from config)", param);
                     datainfo.from_json(val)?
@@ -187,17 +488,23 @@ pub trait ModuleBase {
                     def().into()
                 }
             } else {
-                // must be mandatory
+                // must be mandatory, which is checked when loading the config
                 debug!("initializing value for param {} (from config)", param);
-                datainfo.from_json(&self.config().parameters[param])?
+                let val = self.config().parameters.get(param).ok_or_else(
+                    || Error::config("mandatory parameter missing"))?;
+                datainfo.from_json(val)?
             };
-            cached(self).set(value);
+            let time = self.internals().clock.now();
+            cached(self).set(value, time);
             if !readonly {
                 let value = cached(self).clone();
                 update(self, value)?;
             }
         } else {
-            if !readonly {
+            if let Some(val) = persisted {
+                debug!("initializing value for param {} (from persisted state)", param);
+                self.change(param, val)?;
+            } else if !readonly {
                 if let Some(def) = default {
                     let value = if let Some(val) = self.config().parameters.get(param) {
                         debug!("initializing value for param {} (from config)", param);
@@ -229,23 +536,40 @@ pub trait ModuleBase {
 
     /// Send a general update message back to the dispatcher, which decides if
     /// and where to send it on.
-    fn send_update(&self, param: &str, value: Value, tstamp: f64) {
+    fn send_update(&self, param: &str, value: Value, qualifiers: Value) {
         self.internals().rep_sender.send(
             (None, Msg::Update { module: self.name().into(),
                                  param: param.into(),
-                                 data: json!([value, {"t": tstamp}]) })).unwrap();
+                                 data: json!([value, qualifiers]) })).unwrap();
     }
 
-    /// Updates the regular poll interval to the given value in seconds, and the
-    /// busy poll interval to 1/5 of it.
+    /// Updates the poll interval, in seconds, of all parameters that don't
+    /// have their own interval.
     ///
     /// This is like an ordinary `update_param` method, but on the trait since
     /// it is always implemented the same.
     fn update_pollinterval(&mut self, val: f64) -> Result<(), Error> {
-        self.internals_mut().poll_tickers = (
-            tick(Duration::from_millis((val * 1000.) as u64)),
-            tick(Duration::from_millis((val * 200.) as u64)),
-        );
+        self.internals_mut().pollinterval = val;
+        Ok(())
+    }
+
+    /// Updates the tolerance of the value for reaching the target.
+    fn update_tolerance(&mut self, val: f64) -> Result<(), Error> {
+        self.internals_mut().stabilizer.tolerance = val;
+        Ok(())
+    }
+
+    /// Updates the time, in seconds, that the value must stay within
+    /// tolerance for the target to count as reached.
+    fn update_settle_time(&mut self, val: f64) -> Result<(), Error> {
+        self.internals_mut().stabilizer.settle_time = val;
+        Ok(())
+    }
+
+    /// Updates the time, in seconds, after which a warning is given if the
+    /// target has not been reached.  Zero disables the timeout.
+    fn update_timeout(&mut self, val: f64) -> Result<(), Error> {
+        self.internals_mut().stabilizer.timeout = val;
         Ok(())
     }
 
@@ -257,6 +581,11 @@ pub trait ModuleBase {
     fn run(mut self) where Self: Sized + Module {
         mlzlog::set_thread_prefix(format!("[{}] ", self.name()));
 
+        // Poll times are counted from the start of the module.
+        let mut scheduler = PollScheduler::new(Self::polled_params(),
+                                               self.internals().pollinterval,
+                                               self.internals().clock.now());
+
         // Do initialization steps.  On failure, we panic, which will be caught
         // upstream and retries are scheduled accordingly.
         if let Err(e) = self.init_params() {
@@ -265,6 +594,8 @@ pub trait ModuleBase {
         if let Err(e) = self.setup() {
             panic!("setup failed: {}", e);
         }
+        self.internals().notify_ready();
+        self.internals().setup_done.store(true, Ordering::SeqCst);
 
         // Tell the dispatcher how to describe ourselves.  If the visibility is
         // "none", the module is assumed to be internal-use only.
@@ -274,10 +605,19 @@ pub trait ModuleBase {
                                          structure: self.describe() })).unwrap();
         }
 
-        let mut poll_normal_counter = 0usize;
-        let mut poll_busy_counter = 0usize;
-
         loop {
+            // Poll at most one parameter before looking at requests again,
+            // so that polling many parameters does not delay client requests.
+            let now = self.internals().clock.now();
+            let timeout = match scheduler.next(self.internals().pollinterval, self.is_busy()) {
+                Some((index, due)) if due <= now => {
+                    let param = scheduler.polled(index, now);
+                    self.poll(param);
+                    Duration::from_secs(0)
+                }
+                Some((_, due)) => self.internals().clock.real_duration(due - now),
+                None => Duration::from_secs(1),
+            };
             select! {
                 recv(self.internals().req_receiver) -> res => if let Ok((hid, req)) = res {
                     // These are the only messages that are handled here.  They all
@@ -306,16 +646,7 @@ pub trait ModuleBase {
                     };
                     self.internals().rep_sender.send((Some(hid), rep)).unwrap();
                 },
-                // TODO: decide if polling "atomically" (i.e. all parameters at once)
-                // is ok, since it could delay client requests.
-                recv(self.internals().poll_tickers.0) -> _ => {
-                    self.poll_normal(poll_normal_counter);
-                    poll_normal_counter = poll_normal_counter.wrapping_add(1);
-                },
-                recv(self.internals().poll_tickers.1) -> _ => {
-                    self.poll_busy(poll_busy_counter);
-                    poll_busy_counter = poll_busy_counter.wrapping_add(1);
-                }
+                default(timeout) => {}
             }
         }
     }
@@ -0,0 +1,154 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Loading module classes from shared libraries.
+//!
+//! A plugin is a crate with `crate-type = ["cdylib"]` that exports its
+//! registration function like this:
+//!
+//! ```ignore
+//! fn register(registry: &mut Registry) {
+//!     secop_core::register_classes!(registry; MyDevice);
+//! }
+//!
+//! secop_core::export_plugin!(register);
+//! ```
+//!
+//! Since Rust has no stable ABI, a plugin must be built with the same compiler
+//! and the same version of secop-core as the server; this is checked on load.
+//!
+//! The plugin contains its own copy of secop-core, so it does not share any
+//! global state with the server.  Logging is forwarded to the server's logger,
+//! but modules from plugins cannot connect to other modules of the server via
+//! `Client` (remote connections work).
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::Path;
+use libloading::Library;
+use log::*;
+
+use crate::registry::Registry;
+
+pub use log::{Log, LevelFilter};
+
+/// Version of the plugin interface; increase on changes to `PluginDeclaration`.
+pub const ABI_VERSION: u32 = 2;
+/// The secop-core version the plugin was built against.
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The compiler version the plugin was built with.
+pub const RUSTC_VERSION: &str = env!("SECOP_RUSTC_VERSION");
+
+#[doc(hidden)]
+pub const CORE_VERSION_NUL: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+#[doc(hidden)]
+pub const RUSTC_VERSION_NUL: &str = concat!(env!("SECOP_RUSTC_VERSION"), "\0");
+
+/// Name of the symbol exported by `export_plugin!`.
+const DECLARATION_SYMBOL: &[u8] = b"secop_plugin_declaration\0";
+
+/// The registration function of a plugin, which also gets the server's logger.
+pub type RegisterFn = fn(&mut Registry, &'static dyn Log, LevelFilter);
+
+/// Information exported by each plugin.
+///
+/// The ABI version must stay the first member, so that it can be checked
+/// before anything else.  The versions are C strings, so that they can be
+/// compared before knowing that the plugin was built with the same compiler;
+/// only then is the Rust `register` function called.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub core_version: *const c_char,
+    pub rustc_version: *const c_char,
+    pub register: RegisterFn,
+}
+
+// The version strings point to static data.
+unsafe impl Sync for PluginDeclaration {}
+
+/// Export the given function, taking `&mut Registry`, as the entry point
+/// of a plugin.
+#[macro_export]
+macro_rules! export_plugin {
+    ($register:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static secop_plugin_declaration: $crate::plugin::PluginDeclaration =
+            $crate::plugin::PluginDeclaration {
+                abi_version: $crate::plugin::ABI_VERSION,
+                core_version: $crate::plugin::CORE_VERSION_NUL.as_ptr() as *const _,
+                rustc_version: $crate::plugin::RUSTC_VERSION_NUL.as_ptr() as *const _,
+                register: {
+                    fn __secop_plugin_register(registry: &mut $crate::registry::Registry,
+                                               logger: &'static dyn $crate::plugin::Log,
+                                               level: $crate::plugin::LevelFilter) {
+                        $crate::plugin::init_logging(logger, level);
+                        ($register)(registry)
+                    }
+                    __secop_plugin_register
+                },
+            };
+    };
+}
+
+/// Make the plugin's copy of the `log` crate use the server's logger.
+#[doc(hidden)]
+pub fn init_logging(logger: &'static dyn Log, level: LevelFilter) {
+    // fails if the plugin is loaded twice, which is fine
+    let _ = log::set_logger(logger);
+    log::set_max_level(level);
+}
+
+/// Load a plugin and register its module classes.
+pub fn load_plugin(registry: &mut Registry, path: &Path) -> Result<(), String> {
+    let lib = unsafe { Library::new(path) }.map_err(|e| e.to_string())?;
+    let decl = unsafe {
+        let symbol = lib.get::<*const PluginDeclaration>(DECLARATION_SYMBOL).map_err(
+            |_| "library is not a SECoP plugin".to_string())?;
+        *symbol
+    };
+    // Only the ABI version is known to be at the same place in every
+    // version of the declaration, so check it before reading anything else.
+    let abi_version = unsafe { *(decl as *const u32) };
+    if abi_version != ABI_VERSION {
+        return Err(format!("plugin has interface version {}, but {} is required",
+                           abi_version, ABI_VERSION));
+    }
+    let decl = unsafe { &*decl };
+    let (core_version, rustc_version) = unsafe {
+        (CStr::from_ptr(decl.core_version).to_string_lossy(),
+         CStr::from_ptr(decl.rustc_version).to_string_lossy())
+    };
+    if core_version != CORE_VERSION || rustc_version != RUSTC_VERSION {
+        return Err(format!("plugin was built for secop-core {} with {}, but the server \
+                            uses secop-core {} with {}", core_version, rustc_version,
+                           CORE_VERSION, RUSTC_VERSION));
+    }
+    (decl.register)(registry, log::logger(), log::max_level());
+    info!("loaded plugin {}", path.display());
+    // The registered classes point into the library, so it must never be
+    // unloaded.
+    std::mem::forget(lib);
+    Ok(())
+}
@@ -36,7 +36,7 @@ lazy_static! {
     ^
     (?P<type>[*?\w]+)                 # message type (verb)
     (?: \s
-      (?P<spec>[\w:<>]+)              # spec (object)
+      (?P<spec>[\w:<>.]+)             # spec (object)
       (?: \s
         (?P<json>.*)                  # data (json)
       )?
@@ -47,6 +47,9 @@ lazy_static! {
 
 pub const IDENT_REPLY: &str = "SINE2020&ISSE,SECoP,V2019-09-16,v1.0";
 
+/// UDP port used for discovery requests and node announcements.
+pub const DISCOVERY_PORT: u16 = 10767;
+
 /// Enum that represents any message that can be sent over the network in the
 /// protocol, and some others that are only used internally.
 #[derive(Debug, Clone)]
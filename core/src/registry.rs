@@ -0,0 +1,210 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Registry of the module classes known to the server.
+//!
+//! Crates providing modules register their classes into a `Registry`, which
+//! the server binary then uses to check the config and start the modules.
+//! This way, modules from several crates can be combined:
+//!
+//! ```ignore
+//! let mut registry = Registry::new();
+//! secop_modules::register(&mut registry);
+//! my_drivers::register(&mut registry);
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::thread::Builder;
+use crossbeam_channel::RecvTimeoutError;
+use log::*;
+use serde_json::{Value, json};
+
+use crate::config::Visibility;
+use crate::errors::{Error, ErrorKind};
+use crate::module::{ClassInfo, Module, ModInternals, status_only_description};
+use crate::proto::Msg;
+use crate::types::{StatusConst, StatusType, TypeInfo};
+
+
+/// Register several module classes at once, under their type names.
+///
+/// ```ignore
+/// secop_core::register_classes!(registry; SimCryo, ToellnerPS);
+/// ```
+#[macro_export]
+macro_rules! register_classes {
+    ($registry:expr; $($class:ty),* $(,)?) => {
+        $( $registry.register::<$class>(); )*
+    };
+}
+
+/// The entry points for a single module class.
+#[derive(Clone, Copy)]
+struct ModuleClass {
+    info: fn() -> ClassInfo,
+    run: fn(ModInternals),
+}
+
+#[derive(Clone, Default)]
+pub struct Registry {
+    classes: BTreeMap<String, ModuleClass>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a module class under the name of its type.
+    pub fn register<T: Module>(&mut self) -> &mut Self {
+        self.register_as::<T, _>(T::class_info().name)
+    }
+
+    /// Register a module class under the given class name.  A class that is
+    /// already registered under this name is replaced.
+    pub fn register_as<T: Module, S: Into<String>>(&mut self, class: S) -> &mut Self {
+        let class = class.into();
+        let entry = ModuleClass { info: T::class_info, run: run_module::<T> };
+        if self.classes.insert(class.clone(), entry).is_some() {
+            warn!("module class {} registered twice, using the last one", class);
+        }
+        self
+    }
+
+    /// Return the names of all registered classes, in sorted order.
+    pub fn class_names(&self) -> impl Iterator<Item=&str> {
+        self.classes.keys().map(|s| &**s)
+    }
+
+    /// Get the static information about a module class, if it exists.
+    pub fn class_info(&self, class: &str) -> Option<ClassInfo> {
+        self.classes.get(class).map(|entry| (entry.info)())
+    }
+
+    /// Start the module's own thread.
+    pub fn run_module(&self, internals: ModInternals) -> Result<(), Box<dyn StdError>> {
+        match self.classes.get(&*internals.class()) {
+            Some(entry) => {
+                (entry.run)(internals);
+                Ok(())
+            }
+            None => Err(format!("no such module class: {}", internals.class()).into())
+        }
+    }
+}
+
+
+/// Run a module in its own thread, supervising it.
+///
+/// If the module fails (i.e. panics, also when creation or setup fails), it is
+/// restarted after a delay that increases exponentially with each failure.
+/// Meanwhile, requests are answered with errors, and the status is ERROR.
+/// After the configured maximum number of restarts, the module stays down.
+fn run_module<T: Module>(internals: ModInternals) {
+    let name = internals.name().to_owned();
+    Builder::new().name(name.clone()).spawn(move || {
+        let restart = internals.config().restart.clone();
+        let mut delay = restart.delay;
+        let mut restarts = 0;
+        loop {
+            let started = internals.clock().now();
+            let mut mod_internals = internals.clone();
+            mod_internals.set_restarts(restarts);
+            // The module is recreated after a panic; shared state in the
+            // internals is only touched in single, consistent operations.
+            let payload = match catch_unwind(AssertUnwindSafe(|| {
+                T::create(mod_internals).unwrap_or_else(|e| panic!("init failed: {}", e)).run()
+            })) {
+                Err(payload) => payload,
+                Ok(()) => continue,
+            };
+            let reason = payload.downcast_ref::<String>().map(|s| s.as_str())
+                                .or_else(|| payload.downcast_ref::<&str>().copied())
+                                .unwrap_or("unknown error");
+            // a module that has been running for a while gets a fresh start
+            if internals.clock().now() - started > restart.max_delay {
+                delay = restart.delay;
+            }
+            if restart.max_restarts == Some(restarts) {
+                error!("module {} failed, giving up after {} restarts", name, restarts);
+                let status = format!("{} (gave up after {} restarts)", reason, restarts);
+                serve_while_down(&internals, reason, status, None);
+                return;
+            }
+            restarts += 1;
+            error!("module {} failed, restart #{} in {:.1} s", name, restarts, delay);
+            let status = format!("{} (restart #{} pending)", reason, restarts);
+            serve_while_down(&internals, reason, status, Some(delay));
+            info!("now restarting module {}", name);
+            delay = (delay * restart.factor).min(restart.max_delay);
+        }
+    }).expect("could not start thread");
+}
+
+/// Answer requests to a failed module until the given delay (in seconds) has
+/// passed, or until the server quits if there is no delay.
+fn serve_while_down(internals: &ModInternals, reason: &str, status: String, delay: Option<f64>) {
+    let name = internals.name();
+    let clock = internals.clock();
+    let status = StatusType.to_json((StatusConst::Error, status)).unwrap_or(Value::Null);
+    let status_update = || Msg::Update { module: name.into(), param: "status".into(),
+                                         data: json!([status, {"t": clock.now()}]) };
+    let rep_sender = internals.rep_sender();
+    // The delay counts from the failure, not from when the status was sent.
+    let deadline = delay.map(|delay| clock.now() + delay);
+
+    // Don't let dependent modules wait for us; they will get errors from us
+    // until the restart succeeds.
+    internals.notify_ready();
+
+    // If the module never came up, clients should at least see its status.
+    if !internals.setup_done() && internals.config().visibility != Visibility::None {
+        let _ = rep_sender.send((None, Msg::Describing {
+            id: name.into(), structure: status_only_description(internals.config())
+        }));
+    }
+    let _ = rep_sender.send((None, status_update()));
+
+    loop {
+        let received = match deadline {
+            Some(deadline) => internals.req_receiver().recv_timeout(
+                clock.real_duration(deadline - clock.now())),
+            None => internals.req_receiver().recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let (hid, req) = match received {
+            Ok(msg) => msg,
+            Err(RecvTimeoutError::Timeout) if deadline.map_or(false, |d| clock.now() < d) =>
+                continue,
+            Err(_) => break,
+        };
+        let rep = match req.1 {
+            Msg::Read { ref param, .. } if param == "status" => status_update(),
+            Msg::Read { .. } | Msg::Change { .. } | Msg::Do { .. } => Error::new(
+                ErrorKind::IsError, format!("module has failed: {}", reason)).into_msg(req.0),
+            Msg::Activate { module } => Msg::InitUpdates { module, updates: vec![status_update()] },
+            _ => continue,
+        };
+        let _ = rep_sender.send((Some(hid), rep));
+    }
+}
@@ -26,26 +26,30 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error as StdError;
 use std::io::{Read as IoRead, Write as IoWrite};
-use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
 use std::num::NonZeroU64;
 use std::time::Duration;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
 use log::*;
 use memchr::memchr;
 use derive_new::new;
-use crossbeam_channel::{unbounded, Sender, Receiver, select, tick};
+use crossbeam_channel::{bounded, unbounded, Sender, Receiver, select};
 use serde_json::{Value, json};
-use mlzutil::time::localtime;
 use parking_lot::{const_mutex, Mutex};
 
-use crate::config::ServerConfig;
+use crate::config::{ServerConfig, startup_order};
 use crate::errors::Error;
 use crate::module::ModInternals;
-use crate::proto::{IncomingMsg, Msg, Msg::*, IDENT_REPLY};
+use crate::clock::{Clock, SystemClock};
+use crate::state::StateStore;
+use crate::proto::{IncomingMsg, Msg, Msg::*, IDENT_REPLY, DISCOVERY_PORT};
 
 pub const RECVBUF_LEN: usize = 4096;
 pub const MAX_MSG_LEN: usize = 1024*1024;
+/// How long to wait for a module's setup before starting modules depending on it.
+pub const SETUP_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Handler ID.  This is nonzero so that Option<HandlerId> is the same size.
 pub type HandlerId = NonZeroU64;
@@ -53,6 +57,15 @@ pub type HandlerId = NonZeroU64;
 #[derive(new)]
 pub struct Server {
     config: ServerConfig,
+    /// UDP port to answer discovery requests on, if enabled.
+    #[new(value = "Some(DISCOVERY_PORT)")]
+    discovery_port: Option<u16>,
+    /// Whether to broadcast an announcement of the node on startup.
+    #[new(value = "false")]
+    announce: bool,
+    /// Source of timestamps, for the server and all modules.
+    #[new(value = "Arc::new(SystemClock)")]
+    clock: Arc<dyn Clock>,
 }
 
 // Aliases for all the common channel types.
@@ -78,8 +91,50 @@ pub fn next_handler_id() -> HandlerId {
 }
 
 impl Server {
+    /// Configure UDP discovery: the port to answer requests on (None to
+    /// disable), and whether to announce the node on startup.
+    pub fn discovery(mut self, port: Option<u16>, announce: bool) -> Self {
+        self.discovery_port = port;
+        self.announce = announce;
+        self
+    }
+
+    /// Use the given clock instead of the system clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Answer discovery requests on the UDP socket with the given node info.
+    pub fn udp_responder(udp_sock: UdpSocket, node_info: Value) {
+        mlzlog::set_thread_prefix("UDP: ");
+        info!("discovery responder started");
+        let reply = node_info.to_string();
+        let mut buf = [0u8; RECVBUF_LEN];
+        while let Ok((len, addr)) = udp_sock.recv_from(&mut buf) {
+            let is_request = serde_json::from_slice::<Value>(&buf[..len])
+                .map_or(false, |req| req["SECoP"] == "discover");
+            if is_request {
+                debug!("answering discovery request from {}", addr);
+                if let Err(err) = udp_sock.send_to(reply.as_bytes(), addr) {
+                    warn!("could not answer discovery request from {}: {}", addr, err);
+                }
+            }
+        }
+    }
+
+    /// Broadcast the node info, so that listening clients know we're here.
+    pub fn udp_announce(udp_sock: &UdpSocket, node_info: &Value, port: u16) {
+        let target = (Ipv4Addr::BROADCAST, port);
+        if let Err(err) = udp_sock.set_broadcast(true).and_then(
+            |_| udp_sock.send_to(node_info.to_string().as_bytes(), target))
+        {
+            warn!("could not send discovery announcement: {}", err);
+        }
+    }
+
     /// Listen for connections on the TCP socket and spawn handlers for it.
-    fn tcp_listener(tcp_sock: TcpListener) {
+    fn tcp_listener(tcp_sock: TcpListener, clock: Arc<dyn Clock>) {
         mlzlog::set_thread_prefix("TCP: ");
         info!("listener started");
         let con_sender = CON_SENDER.lock().clone().expect("no server running?");
@@ -91,8 +146,9 @@ impl Server {
             let disp_rep_sender = rep_sender.clone();
             let hid = next_handler_id();
             con_sender.send((hid, disp_rep_sender)).unwrap();
-            thread::spawn(move || Handler::new(hid, stream, addr,
-                                               new_req_sender, rep_sender, rep_receiver).handle());
+            let clock = Arc::clone(&clock);
+            thread::spawn(move || Handler::new(hid, stream, addr, new_req_sender, rep_sender,
+                                               rep_receiver, clock).handle());
         }
     }
 
@@ -111,28 +167,45 @@ impl Server {
         // sending replies from all modules to the dispatcher
         let (rep_sender, rep_receiver) = unbounded();
 
-        // create the modules
+        // create the modules' channels; they are only started after the
+        // dispatcher, since modules can talk to other modules during setup
+        let order = startup_order(&self.config.modules)?;
+        let state = Arc::new(match &self.config.datadir {
+            Some(dir) => StateStore::open(dir.join(format!("{}.state.json",
+                                                           self.config.equipment_id))),
+            None => {
+                info!("no data directory configured, parameters are not persisted");
+                StateStore::in_memory()
+            }
+        });
         let mut active_sets = HashMap::new();
         let mut mod_senders = HashMap::new();
+        let mut mod_internals = HashMap::new();
+        let mut ready_receivers = HashMap::new();
 
         for (name, modcfg) in self.config.modules.drain() {
             // channel to send requests to the module
             let (mod_sender, mod_receiver) = unbounded();
             // replies go via a single one
             let mod_rep_sender = rep_sender.clone();
-            let tickers = (tick(Duration::from_secs(1)), tick(Duration::from_secs(1)));
-            let int = ModInternals::new(name.clone(), modcfg, mod_receiver, mod_rep_sender, tickers);
+            // the module signals completion of its setup here
+            let (ready_sender, ready_receiver) = bounded(1);
+            let int = ModInternals::new(name.clone(), modcfg, mod_receiver, mod_rep_sender,
+                                        ready_sender, Arc::clone(&state))
+                .with_clock(Arc::clone(&self.clock));
             active_sets.insert(name.clone(), HashSet::new());
-            mod_senders.insert(name, mod_sender);
-            mod_runner(int)?;
+            mod_senders.insert(name.clone(), mod_sender);
+            ready_receivers.insert(name.clone(), ready_receiver);
+            mod_internals.insert(name, int);
         }
 
-        let descriptive = json!({
+        let mut descriptive = json!({
             "description": self.config.description,
             "equipment_id": self.config.equipment_id,
             "firmware": concat!("secop-rs ", env!("CARGO_PKG_VERSION")),
             "modules": {}
         });
+        descriptive.as_object_mut().expect("object").extend(self.config.properties.drain());
 
         // create the dispatcher
         let dispatcher = Dispatcher {
@@ -146,9 +219,48 @@ impl Server {
         };
         thread::spawn(move || dispatcher.run());
 
+        // start the modules, each after its dependencies have completed (or failed)
+        // their setup
+        let mut ready = HashSet::new();
+        for name in order {
+            let int = mod_internals.remove(&name).expect("module exists");
+            for dep in &int.config().dependencies {
+                if ready.insert(dep.clone()) {
+                    info!("waiting for module {} to complete setup", dep);
+                    if ready_receivers[dep].recv_timeout(SETUP_TIMEOUT).is_err() {
+                        warn!("module {} did not complete setup, starting {} anyway", dep, name);
+                    }
+                }
+            }
+            mod_runner(int)?;
+        }
+
         // create the TCP socket and start its handler thread
         let tcp_sock = TcpListener::bind(addr)?;
-        thread::spawn(move || Server::tcp_listener(tcp_sock));
+        let tcp_port = tcp_sock.local_addr()?.port();
+        let clock = Arc::clone(&self.clock);
+        thread::spawn(move || Server::tcp_listener(tcp_sock, clock));
+
+        // create the UDP socket for discovery; failure here is not fatal since
+        // another node on this host might already have the port
+        if let Some(udp_port) = self.discovery_port {
+            let node_info = json!({
+                "SECoP": "node",
+                "port": tcp_port,
+                "equipment_id": self.config.equipment_id,
+                "firmware": concat!("secop-rs ", env!("CARGO_PKG_VERSION")),
+                "description": self.config.description,
+            });
+            match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, udp_port)) {
+                Ok(udp_sock) => {
+                    if self.announce {
+                        Server::udp_announce(&udp_sock, &node_info, udp_port);
+                    }
+                    thread::spawn(move || Server::udp_responder(udp_sock, node_info));
+                }
+                Err(err) => warn!("could not bind UDP port {} for discovery: {}", udp_port, err),
+            }
+        }
         Ok(())
     }
 }
@@ -323,17 +435,20 @@ pub struct Handler {
     req_sender: ReqSender,
     /// Sender for outgoing replies, to the sender thread.
     rep_sender: RepSender,
+    /// Source of timestamps for ping replies.
+    clock: Arc<dyn Clock>,
 }
 
 impl Handler {
     pub fn new(hid: HandlerId, client: TcpStream, addr: SocketAddr, req_sender: ReqSender,
-               rep_sender: RepSender, rep_receiver: RepReceiver) -> Handler {
+               rep_sender: RepSender, rep_receiver: RepReceiver,
+               clock: Arc<dyn Clock>) -> Handler {
         // spawn a thread that handles sending replies and events back
         let send_client = client.try_clone().expect("could not clone socket");
         let thread_name = addr.to_string();
         thread::spawn(move || Handler::sender(&thread_name, send_client, rep_receiver));
         mlzlog::set_thread_prefix(format!("[{}] ", addr));
-        Handler { hid, client, req_sender, rep_sender }
+        Handler { hid, client, req_sender, rep_sender, clock }
     }
 
     /// Thread that sends back replies and events to the client.
@@ -365,7 +480,7 @@ impl Handler {
             }
             // but a few of them we can respond to from here
             Ping { token } => {
-                let data = json!([null, {"t": localtime()}]);
+                let data = json!([null, {"t": self.clock.now()}]);
                 self.send_back(Pong { token, data });
             }
             Idn => {
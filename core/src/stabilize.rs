@@ -0,0 +1,116 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Detection of a drivable module reaching its target.
+//!
+//! The `Stabilizer` follows the value of a module after a new target has been
+//! set.  The target counts as reached once the value has stayed within the
+//! tolerance for the settling time.  Until then, the status is BUSY (value not
+//! yet within tolerance) or STABILIZING; if a timeout is set and exceeded, the
+//! status becomes WARN.
+//!
+//! Each module has a stabilizer in its internals.  For modules declared with
+//! `#[stabilizer]`, the derive feeds it with the values read and the targets
+//! written, and its settings can be made configurable by declaring the
+//! `tolerance`, `settle_time` and `timeout` parameters as software-only.  The module's `read_status` then returns
+//! `stabilizer().status()`, possibly overridden by the hardware state.
+
+use crate::types::{Status, StatusConst};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// No target has been set.
+    Idle,
+    /// Approaching the target; since when the value is within tolerance.
+    Approaching { target: f64, started: f64, within_since: Option<f64> },
+    Reached,
+    TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stabilizer {
+    /// Maximum deviation of the value from the target.
+    pub tolerance: f64,
+    /// Seconds the value must stay within tolerance.
+    pub settle_time: f64,
+    /// Seconds after which a warning is given if the target has not been
+    /// reached, or zero for no timeout.
+    pub timeout: f64,
+    state: State,
+}
+
+impl Default for Stabilizer {
+    fn default() -> Self {
+        Self::new(0., 0., 0.)
+    }
+}
+
+impl Stabilizer {
+    pub fn new(tolerance: f64, settle_time: f64, timeout: f64) -> Self {
+        Self { tolerance, settle_time, timeout, state: State::Idle }
+    }
+
+    /// Start approaching a new target.
+    pub fn start(&mut self, target: f64, now: f64) {
+        self.state = State::Approaching { target, started: now, within_since: None };
+    }
+
+    /// Stop approaching the target, e.g. when the module is stopped.
+    pub fn stop(&mut self) {
+        self.state = State::Idle;
+    }
+
+    /// Return true while the target has not been reached (or timed out).
+    pub fn is_active(&self) -> bool {
+        matches!(self.state, State::Approaching { .. })
+    }
+
+    /// Process a new value read at the given time.
+    pub fn update(&mut self, value: f64, now: f64) {
+        if let State::Approaching { target, started, ref mut within_since } = self.state {
+            if (value - target).abs() <= self.tolerance {
+                let since = *within_since.get_or_insert(now);
+                if now - since >= self.settle_time {
+                    self.state = State::Reached;
+                    return;
+                }
+            } else {
+                *within_since = None;
+            }
+            if self.timeout > 0. && now - started > self.timeout {
+                self.state = State::TimedOut;
+            }
+        }
+    }
+
+    /// Return the status corresponding to the current state.
+    pub fn status(&self) -> Status {
+        match self.state {
+            State::Idle => (StatusConst::Idle, "idle".into()),
+            State::Approaching { within_since: None, .. } =>
+                (StatusConst::Busy, "approaching target".into()),
+            State::Approaching { .. } => (StatusConst::Stabilizing, "stabilizing".into()),
+            State::Reached => (StatusConst::Idle, "target reached".into()),
+            State::TimedOut => (StatusConst::Warn, "target not reached within timeout".into()),
+        }
+    }
+}
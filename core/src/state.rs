@@ -0,0 +1,89 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Persistent storage of parameter values across restarts.
+//!
+//! Values of parameters with the `persistent` flag are stored here whenever
+//! they are changed, and written to a state file in the node's data
+//! directory.  On startup, they take precedence over config and defaults.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use log::*;
+use parking_lot::Mutex;
+use serde_json::Value;
+
+type ModuleValues = HashMap<String, HashMap<String, Value>>;
+
+pub struct StateStore {
+    /// The state file, if values should survive a server restart.
+    path: Option<PathBuf>,
+    values: Mutex<ModuleValues>,
+}
+
+impl StateStore {
+    /// Create a store that only keeps values while the server is running,
+    /// i.e. across restarts of single modules.
+    pub fn in_memory() -> Self {
+        Self { path: None, values: Mutex::new(HashMap::new()) }
+    }
+
+    /// Create a store backed by the given file, reading the existing values
+    /// if it exists.  A corrupt state file is ignored.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let values = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("ignoring invalid state file {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self { path: Some(path), values: Mutex::new(values) }
+    }
+
+    /// Get the stored value of a parameter.
+    pub fn get(&self, module: &str, param: &str) -> Option<Value> {
+        self.values.lock().get(module)?.get(param).cloned()
+    }
+
+    /// Store a new value of a parameter, and write the state file if the
+    /// value has changed.
+    pub fn set(&self, module: &str, param: &str, value: Value) {
+        let mut values = self.values.lock();
+        let entry = values.entry(module.into()).or_default();
+        if entry.get(param) == Some(&value) {
+            return;
+        }
+        entry.insert(param.into(), value);
+        if let Some(path) = &self.path {
+            // write to a temporary file first, so that the state file is
+            // never left half-written
+            let tmp_path = path.with_extension("tmp");
+            let data = serde_json::to_vec_pretty(&*values).expect("valid JSON");
+            if let Err(e) = fs::write(&tmp_path, data).and_then(|_| fs::rename(&tmp_path, path)) {
+                error!("could not write state file {}: {}", path.display(), e);
+            }
+        }
+    }
+}
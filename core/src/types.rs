@@ -24,6 +24,9 @@
 //! SECoP data type / data info definitions.
 
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use serde::de::DeserializeOwned;
 use serde::ser::{Serialize, Serializer, SerializeMap};
 use serde_derive::Serialize;
 use serde_json::{Value, json};
@@ -52,6 +55,24 @@ pub trait TypeInfo : Serialize + Clone {
     fn to_json(&self, val: Self::Repr) -> Result<Value, Error>;
     /// Convert an external JSON value, incoming from a connection.
     fn from_json(&self, val: &Value) -> Result<Self::Repr, Error>;
+
+    /// Convert a JSON value in the form produced by serde for Rust data (see
+    /// `Serde`) into the form for the protocol, validating it.  These only
+    /// differ for some types, e.g. enums which serde represents by name.
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.to_json(self.from_json(val)?)
+    }
+    /// Convert an external JSON value into the form expected by serde.
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.to_json(self.from_json(val)?)
+    }
+
+    /// The initial value of parameters with this type, before anything has
+    /// been read or set.  Types whose default `Repr` is not a valid value for
+    /// every instance, like `Matrix`, override this.
+    fn default_value(&self) -> Self::Repr where Self::Repr: Default {
+        Default::default()
+    }
 }
 
 
@@ -243,6 +264,14 @@ impl Scaled {
 impl TypeInfo for Scaled {
     type Repr = f64;
 
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.to_json(val.as_f64().ok_or_else(|| Error::bad_value("expected double"))?)
+    }
+
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        Ok(json!(self.from_json(val)?))
+    }
+
     fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
         let val = (val / self.scale).round() as i64;
         if val >= self.min && val <= self.max {
@@ -331,26 +360,41 @@ impl Blob {
     }
 }
 
-impl TypeInfo for Blob {
-    type Repr = Vec<u8>;
-
-    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+impl Blob {
+    fn check(&self, val: &[u8]) -> Result<(), Error> {
         if val.len() >= self.minbytes && val.len() <= self.maxbytes {
-            Ok(Value::String(base64::encode(&val)))
+            Ok(())
         } else {
             Err(Error::bad_value(format!("expected blob with length between {} and {}",
                                          self.minbytes, self.maxbytes)))
         }
     }
+}
+
+impl TypeInfo for Blob {
+    type Repr = Vec<u8>;
+
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        let v = val.as_array().and_then(|arr| arr.iter().map(|b| {
+            b.as_u64().filter(|&b| b <= 255).map(|b| b as u8)
+        }).collect::<Option<Vec<_>>>()).ok_or_else(|| Error::bad_value("expected array of bytes"))?;
+        self.to_json(v)
+    }
+
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        Ok(json!(self.from_json(val)?))
+    }
+
+    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+        self.check(&val)?;
+        Ok(Value::String(base64::encode(&val)))
+    }
 
     fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
-        if let Some(v) = val.as_str().and_then(|s| base64::decode(s).ok()) {
-            if v.len() >= self.minbytes && v.len() <= self.maxbytes {
-                return Ok(v);
-            }
-        }
-        Err(Error::bad_value(format!("expected base64 coded string with decoded \
-                                      length between {} and {}", self.minbytes, self.maxbytes)))
+        let v = val.as_str().and_then(|s| base64::decode(s).ok()).ok_or_else(
+            || Error::bad_value("expected base64 coded string"))?;
+        self.check(&v)?;
+        Ok(v)
     }
 }
 
@@ -385,22 +429,35 @@ impl Str {
     }
 }
 
+impl Str {
+    /// Lengths are counted in characters.  Unless `isUTF8` is set, only ASCII
+    /// characters are allowed.
+    fn check(&self, val: &str) -> Result<(), Error> {
+        if !self.is_utf8 && !val.is_ascii() {
+            return Err(Error::bad_value("expected string with only ASCII characters"));
+        }
+        let len = val.chars().count();
+        if len >= self.minchars && len <= self.maxchars {
+            Ok(())
+        } else {
+            Err(Error::bad_value(format!("expected string with length between {} and {}",
+                                         self.minchars, self.maxchars)))
+        }
+    }
+}
+
 impl TypeInfo for Str {
     type Repr = String;
 
     fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
-        if val.len() <= self.maxchars {
-            Ok(Value::String(val))
-        } else {
-            Err(Error::bad_value(format!("expected string with length <= {:?}", self.maxchars)))
-        }
+        self.check(&val)?;
+        Ok(Value::String(val))
     }
 
     fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
-        match val.as_str() {
-            Some(v) if v.len() <= self.maxchars => Ok(v.into()),
-            _ => Err(Error::bad_value(format!("expected string with length <= {:?}", self.maxchars)))
-        }
+        let v = val.as_str().ok_or_else(|| Error::bad_value("expected string"))?;
+        self.check(v)?;
+        Ok(v.into())
     }
 }
 
@@ -415,9 +472,32 @@ pub struct ArrayOf<T: TypeInfo> {
     pub members: T,
 }
 
+impl<T: TypeInfo> ArrayOf<T> {
+    fn convert(&self, val: &Value, f: impl Fn(&T, &Value) -> Result<Value, Error>)
+               -> Result<Value, Error> {
+        match val.as_array() {
+            Some(arr) if arr.len() >= self.minlen && arr.len() <= self.maxlen => {
+                arr.iter().enumerate().map(|(i, v)| {
+                    f(&self.members, v).map_err(|e| e.amend(&format!("in item {}", i+1)))
+                }).collect()
+            }
+            _ => Err(Error::bad_value(format!("expected array with length between {} and {}",
+                                              self.minlen, self.maxlen)))
+        }
+    }
+}
+
 impl<T: TypeInfo> TypeInfo for ArrayOf<T> {
     type Repr = Vec<T::Repr>;
 
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.convert(val, T::from_serde)
+    }
+
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.convert(val, T::to_serde)
+    }
+
     fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
         if val.len() >= self.minlen && val.len() <= self.maxlen {
             let v: Result<Vec<_>, _> = val.into_iter().enumerate().map(|(i, v)| {
@@ -451,7 +531,7 @@ macro_rules! impl_tuple {
 
         impl<$($tv: TypeInfo),*> Serialize for $name<$($tv),*> {
             fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-                let members = ($(&self.$idx),+);
+                let members = ($(&self.$idx,)+);
                 let mut map = serializer.serialize_map(Some(2))?;
                 map.serialize_entry("type", "tuple")?;
                 map.serialize_entry("members", &members)?;
@@ -460,7 +540,7 @@ macro_rules! impl_tuple {
         }
 
         impl<$($tv: TypeInfo),*> TypeInfo for $name<$($tv),*> {
-            type Repr = ($($tv::Repr),*);
+            type Repr = ($($tv::Repr,)*);
 
             fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
                 Ok(json!([ $(
@@ -475,23 +555,316 @@ macro_rules! impl_tuple {
                         return Ok((
                             $(
                                 self.$idx.from_json(&arr[$idx])
-                                         .map_err(|e| e.amend(concat!("in item ", $idx))) ?
-                            ),*
+                                         .map_err(|e| e.amend(concat!("in item ", $idx))) ?,
+                            )*
                         ));
                     }
                 }
                 Err(Error::bad_value(concat!("expected array with ",
                                              stringify!($len), " elements")))
             }
+
+            fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+                match val.as_array() {
+                    Some(arr) if arr.len() == $len => Ok(json!([ $(
+                        self.$idx.from_serde(&arr[$idx])
+                                 .map_err(|e| e.amend(concat!("in item ", $idx))) ?
+                    ),* ])),
+                    _ => Err(Error::bad_value(concat!("expected array with ",
+                                                      stringify!($len), " elements")))
+                }
+            }
+
+            fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+                match val.as_array() {
+                    Some(arr) if arr.len() == $len => Ok(json!([ $(
+                        self.$idx.to_serde(&arr[$idx])
+                                 .map_err(|e| e.amend(concat!("in item ", $idx))) ?
+                    ),* ])),
+                    _ => Err(Error::bad_value(concat!("expected array with ",
+                                                      stringify!($len), " elements")))
+                }
+            }
         }
     }
 }
 
+impl_tuple!(Tuple1 => T1 : 1 : 0);
 impl_tuple!(Tuple2 => T1, T2 : 2 : 0, 1);
 impl_tuple!(Tuple3 => T1, T2, T3 : 3 : 0, 1, 2);
 impl_tuple!(Tuple4 => T1, T2, T3, T4 : 4 : 0, 1, 2, 3);
 impl_tuple!(Tuple5 => T1, T2, T3, T4, T5 : 5 : 0, 1, 2, 3, 4);
 impl_tuple!(Tuple6 => T1, T2, T3, T4, T5, T6 : 6 : 0, 1, 2, 3, 4, 5);
+impl_tuple!(Tuple7 => T1, T2, T3, T4, T5, T6, T7 : 7 : 0, 1, 2, 3, 4, 5, 6);
+impl_tuple!(Tuple8 => T1, T2, T3, T4, T5, T6, T7, T8 : 8 : 0, 1, 2, 3, 4, 5, 6, 7);
+impl_tuple!(Tuple9 => T1, T2, T3, T4, T5, T6, T7, T8, T9 : 9 : 0, 1, 2, 3, 4, 5, 6, 7, 8);
+impl_tuple!(Tuple10 => T1, T2, T3, T4, T5, T6, T7, T8, T9, T10 : 10 : 0, 1, 2, 3, 4, 5, 6, 7, 8, 9);
+impl_tuple!(Tuple11 => T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11 : 11 : 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+impl_tuple!(Tuple12 => T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12 : 12 : 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11);
+
+
+/// Element types of a matrix, as given by the `elementtype` property in the
+/// numpy-like notation `<f4`, `>u2` etc.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElementType {
+    I8, U8, I16, U16, I32, U32, I64, U64, F32, F64,
+}
+
+/// Byte order of the elements of a matrix.  Single-byte elements have none,
+/// and are always given as `|u1` or `|i1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little, Big,
+}
+
+/// The typed buffer of a matrix value, with the elements in C order (the last
+/// dimension varies fastest).
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatrixBuffer {
+    I8(Vec<i8>),
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    I32(Vec<i32>),
+    U32(Vec<u32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+macro_rules! impl_matrix_elements {
+    ($($var:ident => $ty:ty, $code:tt;)*) => {
+        impl ElementType {
+            /// All element types.
+            pub const ALL: &'static [ElementType] = &[ $( ElementType::$var ),* ];
+
+            /// Size of a single element in bytes.
+            pub fn size(self) -> usize {
+                match self { $( ElementType::$var => std::mem::size_of::<$ty>() ),* }
+            }
+
+            /// The type code without byte order, e.g. `f4`.
+            pub fn code(self) -> &'static str {
+                match self { $( ElementType::$var => $code ),* }
+            }
+
+            pub fn from_code(code: &str) -> Option<Self> {
+                match code { $( $code => Some(ElementType::$var), )* _ => None }
+            }
+        }
+
+        impl MatrixBuffer {
+            pub fn element_type(&self) -> ElementType {
+                match self { $( MatrixBuffer::$var(_) => ElementType::$var ),* }
+            }
+
+            pub fn len(&self) -> usize {
+                match self { $( MatrixBuffer::$var(v) => v.len() ),* }
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// An empty buffer with the given element type.
+            pub fn empty(element: ElementType) -> Self {
+                match element { $( ElementType::$var => MatrixBuffer::$var(Vec::new()) ),* }
+            }
+
+            fn pack(&self, big_endian: bool) -> Vec<u8> {
+                match self { $(
+                    MatrixBuffer::$var(v) => if big_endian {
+                        v.iter().flat_map(|x| x.to_be_bytes()).collect()
+                    } else {
+                        v.iter().flat_map(|x| x.to_le_bytes()).collect()
+                    }
+                ),* }
+            }
+
+            fn unpack(bytes: &[u8], element: ElementType, big_endian: bool) -> Self {
+                match element { $(
+                    ElementType::$var => MatrixBuffer::$var(
+                        bytes.chunks_exact(std::mem::size_of::<$ty>()).map(|chunk| {
+                            let chunk = chunk.try_into().expect("chunk size");
+                            if big_endian { <$ty>::from_be_bytes(chunk) }
+                            else { <$ty>::from_le_bytes(chunk) }
+                        }).collect())
+                ),* }
+            }
+        }
+
+        $(
+            impl From<Vec<$ty>> for MatrixBuffer {
+                fn from(v: Vec<$ty>) -> Self { MatrixBuffer::$var(v) }
+            }
+        )*
+    }
+}
+
+impl_matrix_elements! {
+    I8 => i8, "i1";
+    U8 => u8, "u1";
+    I16 => i16, "i2";
+    U16 => u16, "u2";
+    I32 => i32, "i4";
+    U32 => u32, "u4";
+    I64 => i64, "i8";
+    U64 => u64, "u8";
+    F32 => f32, "f4";
+    F64 => f64, "f8";
+}
+
+/// The value of a matrix: its extent in each dimension, and the elements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatrixData {
+    pub shape: Vec<usize>,
+    pub data: MatrixBuffer,
+}
+
+impl MatrixData {
+    pub fn new(shape: Vec<usize>, data: impl Into<MatrixBuffer>) -> Self {
+        Self { shape, data: data.into() }
+    }
+
+    /// A matrix without elements, with the given element type and number of
+    /// dimensions.
+    pub fn empty(element: ElementType, dims: usize) -> Self {
+        Self { shape: vec![0; dims], data: MatrixBuffer::empty(element) }
+    }
+}
+
+impl Default for MatrixData {
+    fn default() -> Self {
+        Self { shape: vec![], data: MatrixBuffer::F64(vec![]) }
+    }
+}
+
+/// An n-dimensional numeric array.  On the wire, the elements are packed into
+/// a base64 coded blob, in the byte order given by the element type.
+#[derive(Serialize)]
+#[derive(Clone)]
+#[serde(tag = "type", rename = "matrix")]
+pub struct Matrix {
+    #[serde(serialize_with = "Matrix::serialize_elementtype")]
+    elementtype: (ElementType, ByteOrder),
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    names: Vec<String>,
+    maxlen: Vec<usize>,
+    #[serde(skip_serializing_if = "is_none")]
+    unit: Option<String>,
+}
+
+impl Matrix {
+    pub const fn new() -> Self {
+        Self { elementtype: (ElementType::F64, ByteOrder::Little), names: Vec::new(),
+               maxlen: Vec::new(), unit: None }
+    }
+
+    /// Set the element type and byte order.
+    pub fn element(mut self, element: ElementType, order: ByteOrder) -> Self {
+        self.elementtype = (element, order); self
+    }
+
+    /// Set the element type from its string form, e.g. `"<f4"` for
+    /// little-endian 32-bit floats.
+    ///
+    /// Fails if the element type is not valid.
+    pub fn elementtype(self, val: &str) -> Result<Self, Error> {
+        let invalid = || Error::config(format!("invalid matrix element type {:?}", val));
+        let element = val.get(1..).and_then(ElementType::from_code).ok_or_else(invalid)?;
+        let order = match &val[..1] {
+            "<" => ByteOrder::Little,
+            ">" => ByteOrder::Big,
+            "|" if element.size() == 1 => ByteOrder::Little,
+            _ => return Err(invalid()),
+        };
+        Ok(self.element(element, order))
+    }
+
+    pub fn names<S: Into<String>>(mut self, val: impl IntoIterator<Item=S>) -> Self {
+        self.names = val.into_iter().map(Into::into).collect(); self
+    }
+
+    pub fn maxlen(mut self, val: impl IntoIterator<Item=usize>) -> Self {
+        self.maxlen = val.into_iter().collect(); self
+    }
+
+    pub fn unit(mut self, val: &str) -> Self {
+        self.unit = Some(val.into()); self
+    }
+
+    fn serialize_elementtype<S>(val: &(ElementType, ByteOrder), serializer: S)
+                                -> Result<S::Ok, S::Error> where S: Serializer {
+        let order = match val {
+            (element, _) if element.size() == 1 => "|",
+            (_, ByteOrder::Little) => "<",
+            (_, ByteOrder::Big) => ">",
+        };
+        serializer.collect_str(&format_args!("{}{}", order, val.0.code()))
+    }
+
+    fn check_shape(&self, shape: &[usize]) -> Result<usize, Error> {
+        if shape.len() != self.maxlen.len() {
+            return Err(Error::bad_value(format!("expected matrix with {} dimensions",
+                                                self.maxlen.len())));
+        }
+        if !self.names.is_empty() && self.names.len() != self.maxlen.len() {
+            return Err(Error::bad_value(format!("matrix has {} names for {} dimensions",
+                                                self.names.len(), self.maxlen.len())));
+        }
+        for (i, (&n, &max)) in shape.iter().zip(&self.maxlen).enumerate() {
+            if n > max {
+                return Err(Error::bad_value(format!("expected matrix with length <= {} \
+                                                     in dimension {}", max, i+1)));
+            }
+        }
+        Ok(shape.iter().product())
+    }
+}
+
+impl TypeInfo for Matrix {
+    type Repr = MatrixData;
+
+    fn default_value(&self) -> MatrixData {
+        MatrixData::empty(self.elementtype.0, self.maxlen.len())
+    }
+
+    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+        let (element, order) = self.elementtype;
+        let big_endian = order == ByteOrder::Big;
+        if val.data.element_type() != element {
+            return Err(Error::bad_value(format!("expected matrix with {:?} elements", element)));
+        }
+        let count = self.check_shape(&val.shape)?;
+        if val.data.len() != count {
+            return Err(Error::bad_value(format!("expected {} matrix elements for shape {:?}",
+                                                count, val.shape)));
+        }
+        Ok(json!({"len": val.shape, "blob": base64::encode(val.data.pack(big_endian))}))
+    }
+
+    fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
+        let (element, order) = self.elementtype;
+        let big_endian = order == ByteOrder::Big;
+        let shape = val.get("len").and_then(|v| v.as_array()).and_then(|arr| {
+            arr.iter().map(|n| n.as_u64().map(|n| n as usize)).collect::<Option<Vec<_>>>()
+        });
+        let blob = val.get("blob").and_then(|v| v.as_str()).and_then(|s| base64::decode(s).ok());
+        match (shape, blob) {
+            (Some(shape), Some(blob)) => {
+                let count = self.check_shape(&shape)?;
+                if blob.len() != count * element.size() {
+                    return Err(Error::bad_value(format!(
+                        "expected {} bytes of matrix data for shape {:?}",
+                        count * element.size(), shape)));
+                }
+                Ok(MatrixData { shape, data: MatrixBuffer::unpack(&blob, element, big_endian) })
+            }
+            _ => Err(Error::bad_value("expected object with len array and base64 coded blob"))
+        }
+    }
+}
 
 // Note: There is no type for Command, since it's only a pseudo-type that
 // is not actually validated/converted to.
@@ -499,32 +872,125 @@ impl_tuple!(Tuple6 => T1, T2, T3, T4, T5, T6 : 6 : 0, 1, 2, 3, 4, 5);
 
 /// A generic enum.  On the Rust side, this is represented as an untyped i64.
 ///
-/// You should prefer implementing your own enum class and deriving `TypeInfo`
-/// for it using secop-derive.
+/// For enums known at compile time, you should prefer implementing your own
+/// enum class and deriving `TypeInfo` for it using secop-derive.  This type is
+/// for members that are only known at runtime, e.g. read from the hardware or
+/// the config, and is also used for `datainfo="Enum(name=value, ...)"`.
 #[derive(Serialize)]
-#[derive(Clone)]
+#[derive(Clone, Default)]
 #[serde(tag = "type", rename = "enum")]
 pub struct Enum {
     members: HashMap<String, i64>
 }
 
+impl Enum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a member.
+    ///
+    /// Panics if the member is not valid; use `try_member` or `from_members`
+    /// for members that do not come from the code.
+    pub fn member(self, name: &str, value: i64) -> Self {
+        self.try_member(name, value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a member.
+    ///
+    /// Fails if the name is empty or has surrounding whitespace, or if the
+    /// name or the value is already used by another member.
+    pub fn try_member(mut self, name: &str, value: i64) -> Result<Self, Error> {
+        self.add_member(name.into(), value)?;
+        Ok(self)
+    }
+
+    /// Create an enum from (name, value) pairs.
+    pub fn from_members<S: Into<String>>(members: impl IntoIterator<Item=(S, i64)>)
+                                         -> Result<Self, Error> {
+        let mut result = Self::new();
+        for (name, value) in members {
+            result.add_member(name.into(), value)?;
+        }
+        if result.members.is_empty() {
+            return Err(Error::config("enum must have at least one member"));
+        }
+        Ok(result)
+    }
+
+    /// Create an enum from a config value, which is either a table mapping
+    /// names to values, or a list of names that are numbered from zero.
+    pub fn from_config(config: &Value) -> Result<Self, Error> {
+        match config {
+            Value::Object(map) => Self::from_members(map.iter().map(|(name, value)| {
+                value.as_i64().map(|v| (name.as_str(), v)).ok_or_else(|| Error::config(
+                    format!("value of enum member {:?} must be an integer", name)))
+            }).collect::<Result<Vec<_>, _>>()?),
+            Value::Array(list) => Self::from_members(list.iter().enumerate().map(|(i, name)| {
+                name.as_str().map(|n| (n, i as i64)).ok_or_else(|| Error::config(
+                    format!("name of enum member #{} must be a string", i)))
+            }).collect::<Result<Vec<_>, _>>()?),
+            _ => Err(Error::config("enum members must be given as a table \
+                                    of name = value or a list of names")),
+        }
+    }
+
+    /// Iterate over the (name, value) pairs, ordered by value.
+    pub fn members(&self) -> impl Iterator<Item=(&str, i64)> {
+        let mut members = self.members.iter().map(|(k, &v)| (k.as_str(), v)).collect::<Vec<_>>();
+        members.sort_by_key(|&(_, v)| v);
+        members.into_iter()
+    }
+
+    /// Return the name of the member with the given value.
+    pub fn name_of(&self, value: i64) -> Option<&str> {
+        self.members.iter().find(|(_, &v)| v == value).map(|(k, _)| k.as_str())
+    }
+
+    fn add_member(&mut self, name: String, value: i64) -> Result<(), Error> {
+        if name.is_empty() || name.trim() != name {
+            return Err(Error::config(format!("invalid enum member name {:?}", name)));
+        }
+        if self.members.contains_key(&name) {
+            return Err(Error::config(format!("duplicate enum member name {:?}", name)));
+        }
+        if let Some(other) = self.name_of(value) {
+            return Err(Error::config(format!("enum members {:?} and {:?} have the same \
+                                              value {}", other, name, value)));
+        }
+        self.members.insert(name, value);
+        Ok(())
+    }
+
+    fn not_a_member(&self, what: impl std::fmt::Debug) -> Error {
+        let members = self.members().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>();
+        Error::bad_value(format!("{:?} is not an enum member (expected one of {})",
+                                 what, members.join(", ")))
+    }
+}
+
 impl TypeInfo for Enum {
     type Repr = i64;
 
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        let value = self.from_json(val)?;
+        Ok(json!(self.name_of(value)))
+    }
+
     fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
         if self.members.values().any(|&j| val == j) {
             Ok(json!(val))
         } else {
-            Err(Error::bad_value("integer not an enum member"))
+            Err(self.not_a_member(val))
         }
     }
 
     fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
         if let Some(s) = val.as_str() {
-            self.members.get(s).cloned().ok_or_else(|| Error::bad_value("string not an enum member"))
+            self.members.get(s).cloned().ok_or_else(|| self.not_a_member(s))
         } else if let Some(i) = val.as_i64() {
             if self.members.values().any(|&j| i == j) { Ok(i) }
-            else { Err(Error::bad_value("integer not an enum member")) }
+            else { Err(self.not_a_member(i)) }
         } else {
             Err(Error::bad_value("expected string or integer"))
         }
@@ -532,9 +998,235 @@ impl TypeInfo for Enum {
 }
 
 
+/// Object-safe part of `TypeInfo`, for `AnyType`.
+trait DynTypeInfo: Send + Sync {
+    fn describe(&self) -> Value;
+    fn check(&self, val: &Value) -> Result<Value, Error>;
+    fn dyn_from_serde(&self, val: &Value) -> Result<Value, Error>;
+    fn dyn_to_serde(&self, val: &Value) -> Result<Value, Error>;
+}
+
+impl<T: TypeInfo + Send + Sync> DynTypeInfo for T {
+    fn describe(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    fn check(&self, val: &Value) -> Result<Value, Error> {
+        self.to_json(self.from_json(val)?)
+    }
+
+    fn dyn_from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.from_serde(val)
+    }
+
+    fn dyn_to_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.to_serde(val)
+    }
+}
+
+/// Any data type, with the JSON value as the Rust representation.  Values are
+/// validated and normalized (e.g. enum names are converted to integers).
+#[derive(Clone)]
+pub struct AnyType(Arc<dyn DynTypeInfo>);
+
+impl AnyType {
+    pub fn new<T: TypeInfo + Send + Sync + 'static>(info: T) -> Self {
+        AnyType(Arc::new(info))
+    }
+}
+
+impl Serialize for AnyType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.0.describe().serialize(serializer)
+    }
+}
+
+impl TypeInfo for AnyType {
+    type Repr = Value;
+
+    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+        self.0.check(&val)
+    }
+
+    fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
+        self.0.check(val)
+    }
+
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.0.dyn_from_serde(val)
+    }
+
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.0.dyn_to_serde(val)
+    }
+}
+
+
+/// A struct whose members are given at runtime.  On the Rust side, this is
+/// represented as a JSON object.
+///
+/// For structs known at compile time, you should prefer deriving `TypeInfo`
+/// for a Rust struct, or use `Serde` with a struct implementing serde traits.
+#[derive(Clone, Default)]
+pub struct Struct {
+    members: Vec<(String, AnyType)>,
+    optional: Vec<String>,
+}
+
+impl Struct {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn member<T: TypeInfo + Send + Sync + 'static>(mut self, name: &str, info: T) -> Self {
+        self.members.push((name.into(), AnyType::new(info))); self
+    }
+
+    /// Add a member that can be missing from values.
+    pub fn optional<T: TypeInfo + Send + Sync + 'static>(mut self, name: &str, info: T) -> Self {
+        self.optional.push(name.into());
+        self.member(name, info)
+    }
+
+    fn convert(&self, val: &Value, f: impl Fn(&AnyType, &Value) -> Result<Value, Error>)
+               -> Result<Value, Error> {
+        let obj = val.as_object().ok_or_else(|| Error::bad_value("expected object"))?;
+        if let Some(key) = obj.keys().find(|k| !self.members.iter().any(|m| &m.0 == *k)) {
+            return Err(Error::bad_value(format!("unknown member {} in object", key)));
+        }
+        let mut result = serde_json::Map::new();
+        for (name, info) in &self.members {
+            let optional = self.optional.contains(name);
+            match obj.get(name) {
+                // serde uses null for missing optional values
+                None | Some(Value::Null) if optional => (),
+                None => return Err(Error::bad_value(format!("missing {} in object", name))),
+                Some(v) => {
+                    let v = f(info, v).map_err(|e| e.amend(&format!("in {}", name)))?;
+                    result.insert(name.clone(), v);
+                }
+            }
+        }
+        Ok(Value::Object(result))
+    }
+}
+
+impl Serialize for Struct {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let members = self.members.iter().cloned().collect::<HashMap<_, _>>();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", "struct")?;
+        map.serialize_entry("members", &members)?;
+        if !self.optional.is_empty() {
+            map.serialize_entry("optional", &self.optional)?;
+        }
+        map.end()
+    }
+}
+
+impl TypeInfo for Struct {
+    type Repr = Value;
+
+    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+        self.convert(&val, |m, v| m.from_json(v))
+    }
+
+    fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
+        self.convert(val, |m, v| m.from_json(v))
+    }
+
+    fn from_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.convert(val, AnyType::from_serde)
+    }
+
+    fn to_serde(&self, val: &Value) -> Result<Value, Error> {
+        self.convert(val, AnyType::to_serde)
+    }
+}
+
+
+/// Provides the data type for a Rust type used with `Serde`.
+///
+/// For structs, this can be derived with `#[derive(TypeInfo)]` and the
+/// `#[serde_schema]` attribute, which keeps the schema in sync with the
+/// serialized fields.
+pub trait SerdeSchema: Serialize + DeserializeOwned {
+    fn datainfo() -> AnyType;
+}
+
+/// A data type for any Rust type implementing the serde traits.  Values are
+/// converted through serde, and validated against a schema given as a data
+/// type, for example:
+///
+/// ```ignore
+/// #[derive(TypeInfo, Serialize, Deserialize, Clone, Default, PartialEq)]
+/// #[serde_schema]
+/// struct GasFlow {
+///     #[datainfo="GasType"]
+///     gas: Gas,
+///     #[datainfo="Double(unit=\"ml/min\")"]
+///     flow: f64,
+///     #[datainfo="Str()"]
+///     comment: Option<String>,
+/// }
+/// ```
+///
+/// Then `datainfo="Serde::<GasFlow>()"` can be used for parameters.
+pub struct Serde<T> {
+    schema: AnyType,
+    _repr: PhantomData<fn() -> T>,
+}
+
+impl<T: SerdeSchema> Serde<T> {
+    pub fn new() -> Self {
+        Self::with_schema(T::datainfo())
+    }
+}
+
+impl<T: SerdeSchema> Default for Serde<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Serde<T> {
+    /// Use the given schema instead of the one from `SerdeSchema`.
+    pub fn with_schema<I: TypeInfo + Send + Sync + 'static>(schema: I) -> Self {
+        Self { schema: AnyType::new(schema), _repr: PhantomData }
+    }
+}
+
+impl<T> Clone for Serde<T> {
+    fn clone(&self) -> Self {
+        Self { schema: self.schema.clone(), _repr: PhantomData }
+    }
+}
+
+impl<T> Serialize for Serde<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.schema.serialize(serializer)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> TypeInfo for Serde<T> {
+    type Repr = T;
+
+    fn to_json(&self, val: Self::Repr) -> Result<Value, Error> {
+        let val = serde_json::to_value(&val).map_err(
+            |e| Error::bad_value(format!("cannot serialize value: {}", e)))?;
+        self.schema.from_serde(&val)
+    }
+
+    fn from_json(&self, val: &Value) -> Result<Self::Repr, Error> {
+        serde_json::from_value(self.schema.to_serde(val)?).map_err(
+            |e| Error::bad_value(format!("cannot deserialize value: {}", e)))
+    }
+}
+
+
 // The Status enum, and predefined type.
 
-#[derive(TypeInfo, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(TypeInfo, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum StatusConst {
     Disabled = 0,
@@ -562,6 +1254,13 @@ impl Default for StatusConst {
     }
 }
 
+impl StatusConst {
+    /// Return true for all busy states (300 to 399), including stabilizing.
+    pub fn is_busy(self) -> bool {
+        (300..400).contains(&(self as i32))
+    }
+}
+
 // This could also be a new unit-struct type, but it works as a type
 // alias as well, with less code duplication.  But we need both the
 // type alias and the value alias.
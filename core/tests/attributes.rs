@@ -0,0 +1,229 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for declaring modules with attributes on the handler impl block.
+
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::server::ModRepReceiver;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+/// A power supply.
+#[derive(ModuleBase)]
+#[property(name="_vendor", value="\"ACME\"")]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="output voltage", datainfo="Double()", readonly=true, unit="V")]
+#[param(name="target", doc="target voltage\nmust be positive", datainfo="Double(min=0.0)",
+        readonly=false, default="0.0", unit="V")]
+#[param(name="limit", doc="current limit", datainfo="Double(min=0.0)", readonly=false,
+        swonly=true, default="1.0", unit="A")]
+#[param(name="channel", doc="channel number", datainfo="Int(min=1, max=4)", readonly=true,
+        swonly=true, default="1", visibility="none")]
+#[command(name="stop", doc="stop ramping", argtype="Null", restype="Null")]
+struct Derived {
+    internals: ModInternals,
+    params: DerivedParams,
+}
+
+impl_module!(Derived);
+
+impl DerivedHandlers for Derived {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<f64> { Ok(0.0) }
+    fn read_target(&mut self) -> Result<f64> { Ok(0.0) }
+    fn write_target(&mut self, _: f64) -> Result<()> { Ok(()) }
+    fn do_stop(&mut self, _: ()) -> Result<()> { Ok(()) }
+}
+
+struct Attributed {
+    internals: ModInternals,
+    params: AttributedParams,
+    target: f64,
+    limit: f64,
+}
+
+impl_module!(Attributed, target: 0.0, limit: 0.0);
+
+/// A power supply.
+#[secop::module]
+#[secop::property(name="_vendor", value="\"ACME\"")]
+#[secop::param(name="channel", doc="channel number", datainfo="Int(min=1, max=4)",
+               readonly=true, swonly=true, default="1", visibility="none")]
+impl AttributedHandlers for Attributed {
+    /// status
+    #[secop::param(datainfo="StatusType")]
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+
+    /// output voltage
+    #[secop::param(datainfo="Double()", unit="V")]
+    fn read_value(&mut self) -> Result<f64> { Ok(0.0) }
+
+    fn read_target(&mut self) -> Result<f64> { Ok(self.target) }
+    /// target voltage
+    /// must be positive
+    #[secop::param(datainfo="Double(min=0.0)", default="0.0", unit="V")]
+    fn write_target(&mut self, value: f64) -> Result<()> {
+        self.target = value;
+        Ok(())
+    }
+
+    /// current limit
+    #[secop::param(datainfo="Double(min=0.0)", default="1.0", unit="A")]
+    fn update_limit(&mut self, value: f64) -> Result<()> {
+        self.limit = value;
+        Ok(())
+    }
+
+    /// stop ramping
+    #[secop::command(argtype="Null", restype="Null")]
+    fn do_stop(&mut self, _: ()) -> Result<()> { Ok(()) }
+}
+
+/// Something the generic modules below are parametrized with.
+trait Scale: Default {
+    fn factor(&self) -> f64;
+}
+
+#[derive(Default)]
+struct Milli;
+
+impl Scale for Milli {
+    fn factor(&self) -> f64 { 1e-3 }
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="scaled value", datainfo="Double()", readonly=true)]
+struct GenericDerived<S: Scale> {
+    internals: ModInternals,
+    params: GenericDerivedParams,
+    scale: S,
+}
+
+impl<S: Scale> Module for GenericDerived<S> {
+    fn create(internals: ModInternals) -> Result<Self> {
+        Ok(GenericDerived { internals, params: Default::default(), scale: S::default() })
+    }
+    fn setup(&mut self) -> Result<()> { Ok(()) }
+    fn teardown(&mut self) {}
+}
+
+impl<S: Scale> GenericDerivedHandlers for GenericDerived<S> {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<f64> { Ok(5.0 * self.scale.factor()) }
+}
+
+struct GenericAttributed<S> where S: Scale {
+    internals: ModInternals,
+    params: GenericAttributedParams,
+    scale: S,
+}
+
+impl<S> Module for GenericAttributed<S> where S: Scale {
+    fn create(internals: ModInternals) -> Result<Self> {
+        Ok(GenericAttributed { internals, params: Default::default(), scale: S::default() })
+    }
+    fn setup(&mut self) -> Result<()> { Ok(()) }
+    fn teardown(&mut self) {}
+}
+
+#[secop::module]
+impl<S> GenericAttributedHandlers for GenericAttributed<S> where S: Scale {
+    /// status
+    #[secop::param(datainfo="StatusType")]
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+
+    /// scaled value
+    #[secop::param(datainfo="Double()")]
+    fn read_value(&mut self) -> Result<f64> { Ok(5.0 * self.scale.factor()) }
+}
+
+/// Create and initialize the module.
+fn create<T: Module>() -> (T, ModRepReceiver) {
+    common::create("ps", common::config("PowerSupply", json!({})))
+}
+
+/// Summarize the class info in comparable form.
+fn class_info<T: ModuleBase>() -> (&'static str, Vec<Value>, Vec<Value>) {
+    let info = T::class_info();
+    let mut params = info.params.iter().map(|p| json!([
+        p.name, p.doc, p.datainfo, p.readonly, p.swonly, p.mandatory, p.default,
+        p.module_ref, p.history
+    ])).collect::<Vec<_>>();
+    params.sort_by_key(|p| p[0].as_str().unwrap().to_string());
+    let commands = info.commands.iter().map(|c| json!([
+        c.name, c.doc, c.argument, c.result
+    ])).collect();
+    (info.doc, params, commands)
+}
+
+#[test]
+fn same_implementation() {
+    assert_eq!(class_info::<Derived>(), class_info::<Attributed>());
+
+    let (derived, _rep1) = create::<Derived>();
+    let (attributed, _rep2) = create::<Attributed>();
+    let mut descr1 = derived.describe();
+    let mut descr2 = attributed.describe();
+    assert!(descr1["implementation"].as_str().unwrap().ends_with("::Derived"));
+    assert!(descr2["implementation"].as_str().unwrap().ends_with("::Attributed"));
+    descr1["implementation"] = json!(null);
+    descr2["implementation"] = json!(null);
+    assert_eq!(descr1, descr2);
+    assert_eq!(descr2["interface_classes"], json!(["Drivable"]));
+    assert_eq!(descr2["_vendor"], json!("ACME"));
+    assert_eq!(Derived::polled_params().len(), Attributed::polled_params().len());
+}
+
+#[test]
+fn handlers_are_called() {
+    let (mut module, _rep) = create::<Attributed>();
+    assert_eq!(module.limit, 1.0);
+
+    module.change("target", json!(5.0)).unwrap();
+    assert_eq!(module.target, 5.0);
+    assert_eq!(module.read("target").unwrap()[0], json!(5.0));
+    assert!(module.change("target", json!(-1.0)).is_err());
+
+    module.change("limit", json!(2.5)).unwrap();
+    assert_eq!(module.limit, 2.5);
+    assert!(module.change("value", json!(1.0)).is_err());
+    assert!(module.change("channel", json!(2)).is_err());
+    assert_eq!(module.command("stop", json!(null)).unwrap()[0], json!(null));
+}
+
+#[test]
+fn generic_modules() {
+    let (mut derived, _rep1) = create::<GenericDerived<Milli>>();
+    let (mut attributed, _rep2) = create::<GenericAttributed<Milli>>();
+    assert_eq!(derived.read("value").unwrap()[0], json!(0.005));
+    assert_eq!(attributed.read("value").unwrap()[0], json!(0.005));
+
+    let (_, params1, _) = class_info::<GenericDerived<Milli>>();
+    let (_, params2, _) = class_info::<GenericAttributed<Milli>>();
+    assert_eq!(params1, params2);
+}
@@ -0,0 +1,123 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for running modules with a virtual clock.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crossbeam_channel::bounded;
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::proto::Msg;
+use secop_core::server::ModRepReceiver;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true, poll="0")]
+#[param(name="value", doc="number of reads", datainfo="Int()", readonly=true)]
+struct Counter {
+    internals: ModInternals,
+    params: CounterParams,
+    reads: i64,
+}
+
+impl_module!(Counter, reads: 0);
+
+impl CounterHandlers for Counter {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<i64> {
+        self.reads += 1;
+        Ok(self.reads)
+    }
+}
+
+/// Wait for the next update of the counter's value, returning value and time.
+fn next_update(rep_receiver: &ModRepReceiver, wait: Duration) -> Option<(i64, f64)> {
+    while let Ok((_, msg)) = rep_receiver.recv_timeout(wait) {
+        if let Msg::Update { param, data, .. } = msg {
+            if param == "value" {
+                return Some((data[0].as_i64().unwrap(), data[1]["t"].as_f64().unwrap()));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn virtual_clock() {
+    let clock = VirtualClock::new(1000.);
+    assert_eq!(clock.now(), 1000.);
+    clock.advance(2.5);
+    assert_eq!(clock.now(), 1002.5);
+
+    let clock = Arc::new(clock);
+    let (done_sender, done_receiver) = bounded(1);
+    {
+        let clock = Arc::clone(&clock);
+        thread::spawn(move || {
+            clock.sleep(10.);
+            done_sender.send(()).unwrap();
+        });
+    }
+    assert!(done_receiver.recv_timeout(Duration::from_millis(50)).is_err());
+    clock.advance(10.);
+    assert!(done_receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+}
+
+#[test]
+fn fast_clock() {
+    let clock = VirtualClock::with_speed(0., 1000.);
+    clock.sleep(100.);
+    assert!(clock.now() >= 100.);
+}
+
+#[test]
+fn poll_sequence() {
+    let clock = Arc::new(VirtualClock::new(1000.));
+    let (internals, _req_sender, rep_receiver) =
+        common::internals("counter", common::config("Counter", json!({})));
+    let internals = internals.with_clock(clock.clone());
+    thread::spawn(move || Counter::create(internals).unwrap().run());
+    let wait = Duration::from_secs(5);
+    let short_wait = Duration::from_millis(100);
+
+    // initial read, at the time the module starts
+    assert_eq!(next_update(&rep_receiver, wait), Some((1, 1000.)));
+    // the default poll interval is one second
+    clock.advance(0.5);
+    assert_eq!(next_update(&rep_receiver, short_wait), None);
+    clock.advance(0.5);
+    assert_eq!(next_update(&rep_receiver, wait), Some((2, 1001.)));
+    // missed polls are not made up for
+    clock.advance(3.);
+    assert_eq!(next_update(&rep_receiver, wait), Some((3, 1004.)));
+    assert_eq!(next_update(&rep_receiver, short_wait), None);
+    clock.advance(1.);
+    assert_eq!(next_update(&rep_receiver, wait), Some((4, 1005.)));
+}
@@ -0,0 +1,106 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code, unused_macros)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_channel::{bounded, unbounded};
+use serde_json::{Value, json};
+
+use secop_core::config::{self, ServerConfig};
+use secop_core::module::{ModInternals, Module};
+use secop_core::proto::Msg;
+use secop_core::registry::Registry;
+use secop_core::server::{ModRepReceiver, ReqSender};
+use secop_core::state::StateStore;
+
+/// Implement `Module` for a test module that needs no setup.  Fields other
+/// than `internals` and `params` are given with their initial values.
+macro_rules! impl_module {
+    ($name:ident $(, $field:ident: $value:expr)*) => {
+        impl Module for $name {
+            fn create(internals: ModInternals) -> Result<Self> {
+                Ok($name { internals, params: Default::default() $(, $field: $value)* })
+            }
+            fn setup(&mut self) -> Result<()> { Ok(()) }
+            fn teardown(&mut self) {}
+        }
+    };
+}
+
+/// Return a module config for the class with the given parameters.
+pub fn config(class: &str, params: Value) -> Value {
+    json!({"class": class, "description": "test", "parameters": params})
+}
+
+/// Create the internals for a module, with its state kept in memory.  The
+/// request sender must be kept alive while the module runs.
+pub fn internals(name: &str, config: Value) -> (ModInternals, ReqSender, ModRepReceiver) {
+    internals_with_state(name, config, Arc::new(StateStore::in_memory()))
+}
+
+/// Create the internals for a module with the given state store.
+pub fn internals_with_state(name: &str, config: Value, state: Arc<StateStore>)
+                            -> (ModInternals, ReqSender, ModRepReceiver) {
+    let config = serde_json::from_value(config).unwrap();
+    let (req_sender, req_receiver) = unbounded();
+    let (rep_sender, rep_receiver) = unbounded();
+    let internals = ModInternals::new(name.into(), config, req_receiver, rep_sender,
+                                      bounded(1).0, state);
+    (internals, req_sender, rep_receiver)
+}
+
+/// Create a module from its internals and initialize the parameters.
+pub fn init<T: Module>(internals: ModInternals) -> T {
+    let mut module = T::create(internals).unwrap();
+    module.init_params().unwrap();
+    module
+}
+
+/// Create and initialize a module with the given config.
+pub fn create<T: Module>(name: &str, config: Value) -> (T, ModRepReceiver) {
+    let (internals, _, rep_receiver) = self::internals(name, config);
+    (init(internals), rep_receiver)
+}
+
+/// Return the parameter and data of all pending update messages.
+pub fn updates(rep_receiver: &ModRepReceiver) -> Vec<(String, Value)> {
+    rep_receiver.try_iter().filter_map(|(_, msg)| match msg {
+        Msg::Update { param, data, .. } => Some((param, data)),
+        _ => None
+    }).collect()
+}
+
+/// Write the config to a temporary file and load it with the classes from
+/// the registry.
+pub fn load_config(registry: &mut Registry, config: &str) -> Result<ServerConfig, String> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!("secop-test-{}-{}.cfg", std::process::id(),
+                                                 COUNTER.fetch_add(1, Ordering::SeqCst)));
+    std::fs::write(&path, config).unwrap();
+    let result = config::load_config(&path, registry);
+    std::fs::remove_file(&path).unwrap();
+    result
+}
@@ -0,0 +1,157 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for checking the config file.
+
+use log::*;
+
+use secop_core::prelude::*;
+use secop_core::config::ServerConfig;
+use secop_core::registry::Registry;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true)]
+#[param(name="target", doc="target", datainfo="Double(min=0.0)", readonly=false)]
+#[param(name="devfile", doc="device file", datainfo="Str()", readonly=true, swonly=true,
+        mandatory=true)]
+#[param(name="io", doc="module used for communication", datainfo="Str()", readonly=true,
+        swonly=true, module_ref=true, default="\"\".into()")]
+struct Motor {
+    internals: ModInternals,
+    params: MotorParams,
+}
+
+impl_module!(Motor);
+
+impl MotorHandlers for Motor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<f64> { Ok(0.0) }
+    fn read_target(&mut self) -> Result<f64> { Ok(0.0) }
+    fn write_target(&mut self, _: f64) -> Result<()> { Ok(()) }
+}
+
+/// Load a config with the given node-level entries and modules.
+fn load(node: &str, modules: &str) -> std::result::Result<ServerConfig, String> {
+    let mut registry = Registry::new();
+    registry.register::<Motor>();
+    common::load_config(&mut registry, &format!("description = \"test\"\n{}\n{}", node, modules))
+}
+
+const MOTOR: &str = "[modules.m]\nclass = \"Motor\"\ndescription = \"motor\"\n\
+                     parameters.devfile = \"/dev/null\"\n";
+
+#[test]
+fn node_properties() {
+    let config = load("interface = \"tcp://10767\"\n_contact = \"someone\"\norder = [\"m\"]",
+                      MOTOR).unwrap();
+    assert!(config.equipment_id.starts_with("secop-test-"));
+    assert_eq!(config.properties.len(), 3);
+    assert_eq!(load("equipment_id = \"node1\"", MOTOR).unwrap().equipment_id, "node1");
+
+    let err = |node| load(node, MOTOR).unwrap_err();
+    assert_eq!(err("equipment_id = \"my node\""),
+               "equipment_id \"my node\" must not contain whitespace");
+    assert_eq!(err("contact = \"someone\""),
+               "unknown node property contact (custom properties must start with an underscore)");
+    assert_eq!(err("firmware = \"1.0\""), "node property firmware cannot be configured");
+    assert_eq!(err("timeout = -1"), "invalid value for node property timeout: -1");
+    assert_eq!(err("implementor = 1"), "invalid value for node property implementor: 1");
+    assert_eq!(err("order = [\"m\", \"x\"]"),
+               "invalid value for node property order: [\"m\",\"x\"]");
+}
+
+#[test]
+fn module_properties() {
+    let config = load("", &format!("{}group = \"motors\"\n_vendor = \"ACME\"\n\
+                                    meaning = [\"position\", 10]\n\
+                                    interface_classes = [\"Drivable\"]\n", MOTOR)).unwrap();
+    assert_eq!(config.modules["m"].properties.len(), 3);
+
+    let err = |props: &str| load("", &format!("{}{}\n", MOTOR, props)).unwrap_err();
+    assert_eq!(err("implementation = 1"),
+               "module m: invalid value for module property implementation: 1");
+    assert_eq!(err("features = \"HasOffset\""),
+               "module m: invalid value for module property features: \"HasOffset\"");
+    assert_eq!(err("meaning = [\"position\"]"),
+               "module m: invalid value for module property meaning: [\"position\"]");
+    assert_eq!(err("vendor = \"ACME\""),
+               "module m: unknown module property or key vendor (custom properties must \
+                start with an underscore)");
+    assert_eq!(err("restart.factor = 0.5"),
+               "module m: restart delays must be positive and factor must be at least 1");
+    assert_eq!(err("group = \"m\""), "module name m is not unique amoung modules and groups");
+}
+
+#[test]
+fn module_params() {
+    let err = |params: &str| load("", &format!("{}{}\n", MOTOR, params)).unwrap_err();
+    assert_eq!(err("parameters.speed = 1.0"), "module m: class Motor has no parameter speed");
+    assert_eq!(err("parameters.value = 1.0"),
+               "module m: parameter value is read from hardware and cannot be configured");
+    assert_eq!(err("parameters.target = -1.0"),
+               "module m: invalid value for parameter target: BadValue: expected double \
+                above 0");
+    assert_eq!(err("parameters.devfile = 1"),
+               "module m: invalid value for parameter devfile: BadValue: expected string");
+    assert_eq!(err("history.devfile = 10"),
+               "module m: parameter devfile does not support a history");
+    assert_eq!(err("history.value = 100001"),
+               "module m: history of parameter value cannot be longer than 100000");
+    assert_eq!(err("history.speed = 10"), "module m: class Motor has no parameter speed");
+    assert!(load("", &format!("{}history.target = 100000\n", MOTOR)).is_ok());
+    assert_eq!(load("", "[modules.m]\nclass = \"Motor\"\ndescription = \"motor\"\n")
+               .unwrap_err(), "module m: mandatory parameter devfile is missing");
+    assert_eq!(load("", "[modules.m]\nclass = \"Mover\"\ndescription = \"motor\"\n")
+               .unwrap_err(), "module m: no such module class: Mover");
+
+    // all problems are reported, sorted
+    let config = format!("{}parameters.speed = 1.0\n\
+                          [modules.a]\nclass = \"Motor\"\ndescription = \"motor\"\n", MOTOR);
+    assert_eq!(load("", &config).unwrap_err(),
+               "module a: mandatory parameter devfile is missing\n\
+                module m: class Motor has no parameter speed");
+}
+
+#[test]
+fn module_refs() {
+    let config = format!("{}parameters.io = \"io\"\n\
+                          [modules.io]\nclass = \"Motor\"\ndescription = \"io\"\n\
+                          parameters.devfile = \"/dev/null\"\n\
+                          parameters.io = \"secop://remote:10767/io\"\n", MOTOR);
+    let config = load("", &config).unwrap();
+    assert_eq!(config.modules["m"].dependencies, ["io"]);
+    assert!(config.modules["io"].dependencies.is_empty());
+
+    let err = |params: &str| load("", &format!("{}{}\n", MOTOR, params)).unwrap_err();
+    assert_eq!(err("parameters.io = \"m\""), "module m: parameter io refers to the module itself");
+    assert_eq!(err("parameters.io = \"x\""), "module m: parameter io refers to unknown module x");
+    assert_eq!(load("", &format!("{}parameters.io = \"n\"\n\
+                                  [modules.n]\nclass = \"Motor\"\ndescription = \"n\"\n\
+                                  parameters.devfile = \"/dev/null\"\n\
+                                  parameters.io = \"m\"\n", MOTOR)).unwrap_err(),
+               "dependency cycle between modules: m -> n -> m");
+}
@@ -0,0 +1,187 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Conformance of the data types with the examples of the SECoP specification.
+
+extern crate secop_core;
+
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::types::Scaled;
+use secop_derive::TypeInfo;
+
+/// Check the description of a datainfo, and that valid values are accepted
+/// and sent back unchanged, while invalid values are rejected.
+fn check<T: TypeInfo>(info: T, descr: Value, valid: &[Value], invalid: &[Value]) {
+    assert_eq!(serde_json::to_value(&info).unwrap(), descr);
+    for val in valid {
+        let repr = info.from_json(val).unwrap_or_else(|e| panic!("{} rejected: {}", val, e));
+        assert_eq!(&info.to_json(repr).unwrap(), val, "{} not sent back unchanged", val);
+    }
+    for val in invalid {
+        assert!(info.from_json(val).is_err(), "{} for {} accepted", val, descr);
+    }
+}
+
+#[test]
+fn double() {
+    check(Double::new(), json!({"type": "double"}),
+          &[json!(0.0), json!(-1.5e300), json!(1e-300)],
+          &[json!("1.0"), json!(null), json!([1.0]), json!(true)]);
+    check(Double::new().min(0.0).max(100.0).fmtstr("%.3f"),
+          json!({"type": "double", "min": 0.0, "max": 100.0, "fmtstr": "%.3f"}),
+          &[json!(0.0), json!(42.5), json!(100.0)],
+          &[json!(-0.001), json!(100.001)]);
+    check(Double::new().unit("K").absolute_resolution(0.01).relative_resolution(1e-6),
+          json!({"type": "double", "unit": "K", "absolute_resolution": 0.01,
+                 "relative_resolution": 1e-6}),
+          &[json!(300.0)], &[]);
+    // integers are valid doubles
+    assert_eq!(Double::new().from_json(&json!(5)).unwrap(), 5.0);
+}
+
+#[test]
+fn scaled() {
+    let info = Scaled::new().scale(0.1).min(0).max(2500);
+    check(info.clone(), json!({"type": "scaled", "scale": 0.1, "min": 0, "max": 2500}),
+          &[json!(0), json!(1234), json!(2500)],
+          &[json!(-1), json!(2501), json!(12.5), json!("12")]);
+    assert!((info.from_json(&json!(1234)).unwrap() - 123.4).abs() < 1e-9);
+    assert_eq!(info.to_json(123.44).unwrap(), json!(1234));
+    assert!(info.to_json(250.1).is_err());
+}
+
+#[test]
+fn int() {
+    check(Int::new().min(0).max(100), json!({"type": "int", "min": 0, "max": 100}),
+          &[json!(0), json!(100)],
+          &[json!(-1), json!(101), json!(1.5), json!("1")]);
+    assert!(Int::new().min(0).max(100).to_json(101).is_err());
+}
+
+#[test]
+fn bool() {
+    check(Bool, json!({"type": "bool"}), &[json!(true), json!(false)],
+          &[json!(0), json!(1), json!("true"), json!(null)]);
+}
+
+#[test]
+fn enums() {
+    let info = Enum::new().member("IDLE", 100).member("WARN", 200)
+                          .member("BUSY", 300).member("ERROR", 400);
+    check(info.clone(), json!({"type": "enum", "members": {"IDLE": 100, "WARN": 200,
+                                                           "BUSY": 300, "ERROR": 400}}),
+          &[json!(100), json!(400)],
+          &[json!(0), json!(150), json!("idle"), json!(100.5), json!(null)]);
+    // names are accepted, but the value is always sent
+    assert_eq!(info.from_json(&json!("BUSY")).unwrap(), 300);
+}
+
+#[test]
+fn string() {
+    let info = Str::new().maxchars(80);
+    check(info.clone(), json!({"type": "string", "maxchars": 80}),
+          &[json!(""), json!("x".repeat(80))],
+          &[json!("x".repeat(81)), json!("Ångström"), json!(5), json!(null)]);
+    assert!(info.to_json("x".repeat(81)).is_err());
+    assert!(info.to_json("µA".into()).is_err());
+
+    // lengths are counted in characters, not bytes
+    let info = Str::new().minchars(2).maxchars(3).is_utf8(true);
+    check(info.clone(), json!({"type": "string", "minchars": 2, "maxchars": 3, "isUTF8": true}),
+          &[json!("ab"), json!("µÅ"), json!("ÄÖÜ"), json!("日本語")],
+          &[json!("a"), json!("µ"), json!("abcd"), json!("ÄÖÜß")]);
+    assert!(info.to_json("Ä".into()).is_err());
+}
+
+#[test]
+fn blob() {
+    let info = Blob::new().minbytes(16).maxbytes(16);
+    check(info.clone(), json!({"type": "blob", "minbytes": 16, "maxbytes": 16}),
+          &[json!(base64::encode([0u8; 16]))],
+          &[json!(base64::encode([0u8; 15])), json!(base64::encode([0u8; 17])),
+            json!("not base64!"), json!([0, 1]), json!(null)]);
+    assert!(info.to_json(vec![0; 15]).is_err());
+    assert!(info.to_json(vec![0; 17]).is_err());
+    check(Blob::new().maxbytes(2), json!({"type": "blob", "maxbytes": 2}),
+          &[json!(""), json!(base64::encode([1, 2]))], &[json!(base64::encode([1, 2, 3]))]);
+}
+
+#[test]
+fn array() {
+    let info = ArrayOf { minlen: 3, maxlen: 10, members: Int::new().min(0).max(9) };
+    check(info, json!({"type": "array", "minlen": 3, "maxlen": 10,
+                       "members": {"type": "int", "min": 0, "max": 9}}),
+          &[json!([0, 1, 2]), json!(vec![9; 10])],
+          &[json!([0, 1]), json!(vec![0; 11]), json!([0, 1, 10]), json!({"0": 1}), json!(1)]);
+}
+
+#[test]
+fn tuple() {
+    let info = Tuple2(Int::new().min(0).max(999), Str::new().maxchars(80));
+    check(info, json!({"type": "tuple", "members": [{"type": "int", "min": 0, "max": 999},
+                                                    {"type": "string", "maxchars": 80}]}),
+          &[json!([0, ""]), json!([999, "text"])],
+          &[json!([1000, ""]), json!([0]), json!([0, "", 1]), json!(["", 0]), json!(0)]);
+}
+
+#[derive(TypeInfo, Clone, Copy, Debug, PartialEq)]
+enum OnOff {
+    Off = 0,
+    On = 1,
+}
+
+#[derive(TypeInfo, Clone, Debug, PartialEq)]
+struct Point {
+    #[datainfo="Double()"]
+    y: f64,
+    #[datainfo="OnOffType"]
+    x: OnOff,
+}
+
+#[derive(TypeInfo, Clone, Debug, PartialEq)]
+struct Setting {
+    #[datainfo="Int(min=0, max=10)"]
+    level: i64,
+    #[datainfo="Str(maxchars=10)"]
+    comment: Option<String>,
+}
+
+#[test]
+fn structs() {
+    check(PointType, json!({"type": "struct",
+                            "members": {"y": {"type": "double"},
+                                        "x": {"type": "enum", "members": {"On": 1, "Off": 0}}}}),
+          &[json!({"x": 0, "y": 1.5}), json!({"x": 1, "y": -1.0})],
+          &[json!({"x": 0}), json!({"x": 2, "y": 1.5}), json!([0, 1.5]), json!(null)]);
+    assert_eq!(PointType.from_json(&json!({"x": "On", "y": 0})).unwrap(),
+               Point { x: OnOff::On, y: 0.0 });
+
+    check(SettingType, json!({"type": "struct",
+                              "members": {"level": {"type": "int", "min": 0, "max": 10},
+                                          "comment": {"type": "string", "maxchars": 10}},
+                              "optional": ["comment"]}),
+          &[json!({"level": 1}), json!({"level": 10, "comment": "ok"})],
+          &[json!({"comment": "ok"}), json!({"level": 11}),
+            json!({"level": 1, "comment": "much too long"})]);
+}
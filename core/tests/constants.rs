@@ -0,0 +1,89 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for constant parameters.
+
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::proto::Msg;
+use secop_core::server::ModRepReceiver;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true)]
+#[param(name="serial", doc="serial number", datainfo="Str(maxchars=16)", readonly=true,
+        constant=true, mandatory=true)]
+#[param(name="channels", doc="number of channels", datainfo="Int(min=1)", readonly=true,
+        constant=true, default="4")]
+struct Sensor {
+    internals: ModInternals,
+    params: SensorParams,
+}
+
+impl_module!(Sensor);
+
+impl SensorHandlers for Sensor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<f64> { Ok(1.0) }
+}
+
+fn create(params: Value) -> (Sensor, ModRepReceiver) {
+    common::create("sensor", common::config("Sensor", params))
+}
+
+#[test]
+fn description() {
+    let (sensor, _rep) = create(json!({"serial": "SN-123"}));
+    let descr = sensor.describe();
+    assert_eq!(descr["accessibles"]["serial"]["constant"], json!("SN-123"));
+    assert_eq!(descr["accessibles"]["serial"]["readonly"], json!(true));
+    assert_eq!(descr["accessibles"]["channels"]["constant"], json!(4));
+    assert!(descr["accessibles"]["value"].get("constant").is_none());
+
+    let (sensor, _rep) = create(json!({"serial": "SN-456", "channels": 2}));
+    let descr = sensor.describe();
+    assert_eq!(descr["accessibles"]["serial"]["constant"], json!("SN-456"));
+    assert_eq!(descr["accessibles"]["channels"]["constant"], json!(2));
+}
+
+#[test]
+fn no_polling_and_updates() {
+    let (mut sensor, rep) = create(json!({"serial": "SN-123"}));
+    let polled = Sensor::polled_params().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(polled, ["status", "value"]);
+
+    let updated = |msgs: Vec<Msg>| msgs.into_iter().filter_map(|msg| match msg {
+        Msg::Update { param, .. } => Some(param),
+        _ => None
+    }).collect::<Vec<_>>();
+    assert_eq!(updated(rep.try_iter().map(|(_, msg)| msg).collect()), ["status", "value"]);
+    assert_eq!(updated(sensor.activate_updates()), ["status", "value"]);
+
+    assert!(sensor.change("serial", json!("SN-789")).is_err());
+    assert_eq!(sensor.read("serial").unwrap()[0], json!("SN-123"));
+}
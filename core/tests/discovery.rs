@@ -0,0 +1,61 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for UDP discovery over loopback.
+
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+use serde_json::json;
+
+use secop_core::client::discover_at;
+use secop_core::server::Server;
+
+#[test]
+fn discover_loopback() {
+    let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = sock.local_addr().unwrap();
+    let info = json!({"SECoP": "node", "port": 12345, "equipment_id": "test_node",
+                      "firmware": "secop-rs", "description": "a test node"});
+    thread::spawn(move || Server::udp_responder(sock, info));
+
+    let nodes = discover_at(addr, Duration::from_millis(500)).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].addr, "127.0.0.1:12345".parse().unwrap());
+    assert_eq!(nodes[0].equipment_id, "test_node");
+    assert_eq!(nodes[0].description, "a test node");
+    assert_eq!(nodes[0].firmware, "secop-rs");
+}
+
+#[test]
+fn ignore_invalid_requests() {
+    let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = sock.local_addr().unwrap();
+    let info = json!({"SECoP": "node", "port": 12345});
+    thread::spawn(move || Server::udp_responder(sock, info));
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    client.send_to(br#"{"SECoP": "node"}"#, addr).unwrap();
+    client.send_to(b"garbage", addr).unwrap();
+    assert!(client.recv_from(&mut [0; 1024]).is_err());
+}
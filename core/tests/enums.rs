@@ -0,0 +1,174 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for enums built at runtime.
+
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::registry::Registry;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[test]
+fn builder() {
+    let info = Enum::new().member("off", 0).member("on", 1).member("auto", 5);
+    assert_eq!(serde_json::to_value(&info).unwrap(),
+               json!({"type": "enum", "members": {"off": 0, "on": 1, "auto": 5}}));
+    assert_eq!(info.members().collect::<Vec<_>>(), [("off", 0), ("on", 1), ("auto", 5)]);
+    assert_eq!(info.name_of(5), Some("auto"));
+
+    assert_eq!(info.from_json(&json!("auto")).unwrap(), 5);
+    assert_eq!(info.from_json(&json!(1)).unwrap(), 1);
+    assert_eq!(info.to_json(0).unwrap(), json!(0));
+
+    let err = info.from_json(&json!("manual")).unwrap_err().to_string();
+    assert!(err.contains("\"manual\" is not an enum member (expected one of \
+                          off=0, on=1, auto=5)"), "{}", err);
+    assert!(info.from_json(&json!(2)).is_err());
+    assert!(info.to_json(2).is_err());
+    assert!(info.from_json(&json!(1.5)).is_err());
+}
+
+#[test]
+#[should_panic(expected = "enum members \"b\" and \"c\" have the same value 1")]
+fn builder_duplicate() {
+    let _ = Enum::new().member("a", 0).member("b", 1).member("c", 1);
+}
+
+#[test]
+fn builder_errors() {
+    let info = Enum::new().try_member("a", 0).and_then(|e| e.try_member("b", 1)).unwrap();
+    assert_eq!(info.members().collect::<Vec<_>>(), [("a", 0), ("b", 1)]);
+    let err = |res: Result<Enum>| res.err().unwrap().to_string();
+    assert!(err(info.clone().try_member("c", 1)).contains("have the same value 1"));
+    assert!(err(info.clone().try_member("a", 2)).contains("duplicate enum member name \"a\""));
+    assert!(err(info.try_member(" c", 2)).contains("invalid enum member name"));
+}
+
+#[test]
+fn from_config() {
+    let info = Enum::from_config(&json!({"He": 1, "N2": 2})).unwrap();
+    assert_eq!(info.members().collect::<Vec<_>>(), [("He", 1), ("N2", 2)]);
+    let info = Enum::from_config(&json!(["A", "B", "C"])).unwrap();
+    assert_eq!(info.members().collect::<Vec<_>>(), [("A", 0), ("B", 1), ("C", 2)]);
+
+    let err = |cfg| Enum::from_config(&cfg).err().unwrap().to_string();
+    assert!(err(json!({"He": "one"})).contains("value of enum member \"He\" must be an integer"));
+    assert!(err(json!(["A", 1])).contains("name of enum member #1 must be a string"));
+    assert!(err(json!({"A": 1, "B": 1})).contains("have the same value 1"));
+    assert!(err(json!(["A", "A"])).contains("duplicate enum member name \"A\""));
+    assert!(err(json!([""])).contains("invalid enum member name"));
+    assert!(err(json!([])).contains("at least one member"));
+    assert!(err(json!("A, B")).contains("must be given as a table"));
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="mode", doc="mode", datainfo=r#"Enum(off=0, on=1, "very fast"=-1)"#,
+        readonly=false, swonly=true, default="0")]
+#[param(name="gas", doc="selected gas", datainfo="Enum(none=0)", readonly=false)]
+struct Selector {
+    internals: ModInternals,
+    params: SelectorParams,
+    gas: i64,
+}
+
+impl_module!(Selector, gas: 0);
+
+impl SelectorHandlers for Selector {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn update_mode(&mut self, _: i64) -> Result<()> { Ok(()) }
+    fn read_gas(&mut self) -> Result<i64> { Ok(self.gas) }
+    fn write_gas(&mut self, value: i64) -> Result<()> {
+        self.gas = value;
+        Ok(())
+    }
+}
+
+#[test]
+fn datainfo_attribute() {
+    let info = Selector::class_info();
+    let mode = info.params.iter().find(|p| p.name == "mode").unwrap();
+    assert_eq!(mode.datainfo, json!({"type": "enum",
+                                     "members": {"off": 0, "on": 1, "very fast": -1}}));
+    assert!((mode.check)(&json!("very fast")).is_ok());
+    assert!((mode.check)(&json!("slow")).is_err());
+}
+
+#[test]
+fn config_members_invalid() {
+    // invalid members from the config are an error, not a panic
+    let mut config = common::config("Selector", json!({"gas": "He"}));
+    config["enums"] = json!({"gas": ["He", "He"]});
+    let (internals, _, _rep) = common::internals("sel", config);
+    let err = Selector::create(internals).unwrap().init_params().unwrap_err().to_string();
+    assert!(err.contains("duplicate enum member name \"He\" (while initializing parameter gas)"),
+            "{}", err);
+}
+
+#[test]
+fn config_members() {
+    let config = json!({"class": "Selector", "description": "test",
+                        "parameters": {"gas": "N2"}, "enums": {"gas": {"He": 1, "N2": 2}}});
+    let (mut selector, _rep) = common::create::<Selector>("sel", config);
+    assert_eq!(selector.params.gas.info.members().collect::<Vec<_>>(), [("He", 1), ("N2", 2)]);
+    assert_eq!(selector.describe()["accessibles"]["gas"]["datainfo"],
+               json!({"type": "enum", "members": {"He": 1, "N2": 2}}));
+    assert_eq!(selector.read("gas").unwrap()[0], json!(2));
+    assert!(selector.change("gas", json!("Xe")).is_err());
+    assert_eq!(selector.change("gas", json!("He")).unwrap()[0], json!(1));
+}
+
+#[test]
+fn config_members_checked() {
+    let mut registry = Registry::new();
+    registry.register::<Selector>();
+    let load = |registry: &mut Registry, gas: &str, enums: &str| common::load_config(
+        registry, &format!("description = \"test\"\n\
+                            [modules.sel]\n\
+                            class = \"Selector\"\n\
+                            description = \"gas selector\"\n\
+                            parameters.gas = {}\n\
+                            {}\n", gas, enums));
+
+    let config = load(&mut registry, "\"N2\"", "enums.gas = [\"He\", \"N2\"]").unwrap();
+    assert_eq!(config.modules["sel"].enums["gas"], json!(["He", "N2"]));
+
+    assert_eq!(load(&mut registry, "\"Xe\"", "enums.gas = [\"He\", \"N2\"]").unwrap_err(),
+               "module sel: invalid value for parameter gas: BadValue: \"Xe\" is not an enum \
+                member (expected one of He=0, N2=1)");
+    // without members from the config, the members from the code apply
+    assert_eq!(load(&mut registry, "\"N2\"", "").unwrap_err(),
+               "module sel: invalid value for parameter gas: BadValue: \"N2\" is not an enum \
+                member (expected one of none=0)");
+    assert_eq!(load(&mut registry, "\"He\"", "enums.gas = [\"He\", \"He\"]").unwrap_err(),
+               "module sel: invalid enum members for parameter gas: InternalError: \
+                duplicate enum member name \"He\"");
+    assert_eq!(load(&mut registry, "0", "enums.status = [\"ok\"]").unwrap_err(),
+               "module sel: parameter status does not take enum members from the config");
+    assert_eq!(load(&mut registry, "0", "enums.speed = [\"slow\"]").unwrap_err(),
+               "module sel: class Selector has no parameter speed");
+}
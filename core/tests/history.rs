@@ -0,0 +1,131 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for the history of parameter values.
+
+use std::sync::Arc;
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::history::History;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[test]
+fn ring_buffer() {
+    let mut history = History::new(3);
+    assert_eq!(history.stats(0.), None);
+    for (t, v) in [(1., 1.), (2., 2.), (3., 3.), (4., 6.)] {
+        history.push(t, v);
+    }
+    assert_eq!(history.start(), Some(2.));
+    assert_eq!(history.since(0.).collect::<Vec<_>>(), [(2., 2.), (3., 3.), (4., 6.)]);
+    // the value at the start of the window is included
+    assert_eq!(history.since(3.5).collect::<Vec<_>>(), [(3., 3.), (4., 6.)]);
+    assert_eq!(history.since(5.).collect::<Vec<_>>(), [(4., 6.)]);
+
+    let stats = history.stats(0.).unwrap();
+    assert_eq!((stats.count, stats.min, stats.max, stats.mean), (3, 2., 6., 11. / 3.));
+    assert!((stats.stddev - (26f64 / 9.).sqrt()).abs() < 1e-12);
+}
+
+#[test]
+fn stats_precision() {
+    // large offset, small spread
+    let mut history = History::new(10);
+    for (t, v) in [(1., 1e6 + 0.001), (2., 1e6 - 0.001), (3., 1e6 + 0.001), (4., 1e6 - 0.001)] {
+        history.push(t, v);
+    }
+    let stats = history.stats(0.).unwrap();
+    assert_eq!(stats.mean, 1e6);
+    assert!((stats.stddev - 0.001).abs() < 1e-9);
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true, history="100")]
+#[param(name="other", doc="other value", datainfo="Double()", readonly=true)]
+struct Sensor {
+    internals: ModInternals,
+    params: SensorParams,
+    value: f64,
+}
+
+impl_module!(Sensor, value: 0.0);
+
+impl SensorHandlers for Sensor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<f64> { Ok(self.value) }
+    fn read_other(&mut self) -> Result<f64> { Ok(0.0) }
+}
+
+#[test]
+fn get_history() {
+    let clock = Arc::new(VirtualClock::new(100.));
+    let (internals, _, _rep_receiver) =
+        common::internals("sensor", common::config("Sensor", json!({})));
+    let mut sensor: Sensor = common::init(internals.with_clock(clock.clone()));
+    for value in [1.0, 2.0, 4.0] {
+        clock.advance(1.);
+        sensor.value = value;
+        sensor.read("value").unwrap();
+    }
+    assert_eq!(sensor.params.value.history().unwrap().stats(102.5).unwrap().max, 4.0);
+    assert!(sensor.params.other.history().is_none());
+
+    let reply = sensor.command("get_history", json!({"param": "value", "window": 1.0})).unwrap();
+    assert_eq!(reply[0], json!({"t": [102.0, 103.0], "values": [2.0, 4.0],
+                                "min": 2.0, "max": 4.0, "mean": 3.0, "stddev": 1.0}));
+    assert_eq!(reply[1]["t"], json!(103.0));
+    // without a window, the initial value is included
+    let reply = sensor.command("get_history", json!({"param": "value"})).unwrap();
+    assert_eq!(reply[0]["t"], json!([100.0, 101.0, 102.0, 103.0]));
+    assert!(sensor.command("get_history", json!({"param": "other"})).is_err());
+    assert!(sensor.describe()["accessibles"]["get_history"].is_object());
+}
+
+#[test]
+fn config_history() {
+    let create = |history| {
+        let mut config = common::config("Sensor", json!({}));
+        config["history"] = history;
+        let (internals, _, _rep_receiver) = common::internals("sensor", config);
+        common::init::<Sensor>(internals)
+    };
+
+    // the config can enable the history of other numeric parameters
+    let mut sensor = create(json!({"other": 10}));
+    assert!(sensor.params.value.history().is_some());
+    assert_eq!(sensor.params.other.history().unwrap().stats(0.).unwrap().count, 1);
+    assert!(sensor.command("get_history", json!({"param": "other"})).is_ok());
+    assert!(sensor.command("get_history", json!({"param": "status"})).is_err());
+
+    // and disable it, then the command is not described
+    let mut sensor = create(json!({"value": 0}));
+    assert!(sensor.params.value.history().is_none());
+    assert!(sensor.command("get_history", json!({"param": "value"})).is_err());
+    assert!(sensor.describe()["accessibles"].get("get_history").is_none());
+}
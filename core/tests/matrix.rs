@@ -0,0 +1,203 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for the matrix data type.
+
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::types::{ByteOrder, ElementType, MatrixBuffer};
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[test]
+fn packing() {
+    let info = Matrix::new().element(ElementType::I16, ByteOrder::Little).names(["x", "y"]).maxlen([3, 2]);
+    assert_eq!(serde_json::to_value(&info).unwrap(),
+               json!({"type": "matrix", "elementtype": "<i2", "names": ["x", "y"],
+                      "maxlen": [3, 2]}));
+
+    let data = MatrixData::new(vec![3, 2], vec![1i16, 2, 3, 4, -1, 256]);
+    let val = info.to_json(data.clone()).unwrap();
+    let blob = base64::encode([1, 0, 2, 0, 3, 0, 4, 0, 0xff, 0xff, 0, 1]);
+    assert_eq!(val, json!({"len": [3, 2], "blob": blob}));
+    assert_eq!(info.from_json(&val).unwrap(), data);
+
+    // smaller than the maximum is fine, as is an empty matrix
+    let small = MatrixData::new(vec![1, 2], vec![7i16, 8]);
+    assert_eq!(info.from_json(&info.to_json(small.clone()).unwrap()).unwrap(), small);
+    let empty = MatrixData::new(vec![0, 2], Vec::<i16>::new());
+    assert_eq!(info.from_json(&info.to_json(empty.clone()).unwrap()).unwrap(), empty);
+}
+
+#[test]
+fn endianness() {
+    let little = Matrix::new().elementtype("<f4").unwrap().maxlen([2]);
+    let big = Matrix::new().elementtype(">f4").unwrap().maxlen([2]);
+    assert_eq!(serde_json::to_value(&big).unwrap()["elementtype"], ">f4");
+
+    let data = MatrixData::new(vec![2], vec![1.0f32, -2.5]);
+    let le = little.to_json(data.clone()).unwrap();
+    let be = big.to_json(data.clone()).unwrap();
+    assert_eq!(le["blob"], base64::encode([0, 0, 0x80, 0x3f, 0, 0, 0x20, 0xc0]));
+    assert_eq!(be["blob"], base64::encode([0x3f, 0x80, 0, 0, 0xc0, 0x20, 0, 0]));
+    assert_eq!(little.from_json(&le).unwrap(), data);
+    assert_eq!(big.from_json(&be).unwrap(), data);
+    // the same bytes mean something else in the other byte order
+    assert_ne!(big.from_json(&le).unwrap(), data);
+
+    // single bytes have no byte order
+    let bytes = Matrix::new().elementtype("|u1").unwrap().maxlen([4]);
+    assert_eq!(serde_json::to_value(&bytes).unwrap()["elementtype"], "|u1");
+    let bytes = Matrix::new().elementtype("<u1").unwrap().maxlen([4]);
+    assert_eq!(serde_json::to_value(&bytes).unwrap()["elementtype"], "|u1");
+    let val = bytes.to_json(MatrixData::new(vec![3], vec![1u8, 2, 3])).unwrap();
+    assert_eq!(val["blob"], base64::encode([1, 2, 3]));
+
+    assert_eq!(MatrixBuffer::from(vec![0u64]).element_type(), ElementType::U64);
+    assert_eq!(ElementType::F64.size(), 8);
+}
+
+#[test]
+fn invalid_elementtype() {
+    for val in ["<c8", "|f4", "f4", "", "\u{e9}u1"] {
+        let err = Matrix::new().elementtype(val).err().unwrap();
+        assert_eq!(err.to_string(),
+                   format!("InternalError: invalid matrix element type {:?}", val));
+    }
+}
+
+#[test]
+fn limits() {
+    let info = Matrix::new().element(ElementType::U16, ByteOrder::Little).maxlen([2, 2]);
+    let blob = |n: usize| json!(base64::encode(vec![0u8; n]));
+
+    // outgoing values
+    assert!(info.to_json(MatrixData::new(vec![3, 1], vec![0u16; 3])).is_err());
+    assert!(info.to_json(MatrixData::new(vec![4], vec![0u16; 4])).is_err());
+    assert!(info.to_json(MatrixData::new(vec![2, 2], vec![0u16; 3])).is_err());
+    assert!(info.to_json(MatrixData::new(vec![2, 2], vec![0i16; 4])).is_err());
+
+    // incoming values
+    assert!(info.from_json(&json!({"len": [2, 2], "blob": blob(8)})).is_ok());
+    assert!(info.from_json(&json!({"len": [2, 3], "blob": blob(12)})).is_err());
+    assert!(info.from_json(&json!({"len": [2], "blob": blob(4)})).is_err());
+    assert!(info.from_json(&json!({"len": [2, 2], "blob": blob(7)})).is_err());
+    assert!(info.from_json(&json!({"len": [2, -1], "blob": blob(0)})).is_err());
+    assert!(info.from_json(&json!({"len": [1, 1], "blob": "not base64!"})).is_err());
+    assert!(info.from_json(&json!({"len": [1, 1]})).is_err());
+    assert!(info.from_json(&json!([1, 2, 3, 4])).is_err());
+
+    // names must match the dimensions
+    let named = info.names(["x", "y", "z"]);
+    let err = named.from_json(&json!({"len": [2, 2], "blob": blob(8)})).unwrap_err();
+    assert_eq!(err.to_string(), "BadValue: matrix has 3 names for 2 dimensions");
+    assert!(named.to_json(MatrixData::new(vec![2, 2], vec![0u16; 4])).is_err());
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="image",
+        datainfo=r#"Matrix(elementtype="<u4", names=["x", "y"], maxlen=[640, 480], unit="counts")"#,
+        readonly=true)]
+struct Detector {
+    internals: ModInternals,
+    params: DetectorParams,
+}
+
+impl_module!(Detector);
+
+impl DetectorHandlers for Detector {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<MatrixData> {
+        Ok(MatrixData::new(vec![2, 2], vec![0u32; 4]))
+    }
+}
+
+#[test]
+fn datainfo_attribute() {
+    let info = Detector::class_info();
+    let value = info.params.iter().find(|p| p.name == "value").unwrap();
+    assert_eq!(value.datainfo, json!({"type": "matrix", "elementtype": "<u4",
+                                      "names": ["x", "y"], "maxlen": [640, 480],
+                                      "unit": "counts"}));
+    assert!((value.check)(&json!({"len": [1, 1], "blob": "AAAAAA=="})).is_ok());
+    assert!((value.check)(&json!({"len": [641, 1], "blob": ""})).is_err());
+}
+
+#[test]
+fn initial_value() {
+    // before the first read, the value is an empty matrix of the declared type
+    let (internals, _, _) = common::internals("det", common::config("Detector", json!({})));
+    let detector = Detector::create(internals).unwrap();
+    assert_eq!(*detector.params.value, MatrixData::empty(ElementType::U32, 2));
+    assert_eq!(detector.params.value.to_json().unwrap(), json!({"len": [0, 0], "blob": ""}));
+}
+
+/// Module with a matrix parameter for each element type known to the derive.
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="i1", doc="i1", datainfo=r#"Matrix(elementtype="<i1")"#, readonly=true)]
+#[param(name="u1", doc="u1", datainfo=r#"Matrix(elementtype="<u1")"#, readonly=true)]
+#[param(name="i2", doc="i2", datainfo=r#"Matrix(elementtype="<i2")"#, readonly=true)]
+#[param(name="u2", doc="u2", datainfo=r#"Matrix(elementtype="<u2")"#, readonly=true)]
+#[param(name="i4", doc="i4", datainfo=r#"Matrix(elementtype="<i4")"#, readonly=true)]
+#[param(name="u4", doc="u4", datainfo=r#"Matrix(elementtype="<u4")"#, readonly=true)]
+#[param(name="i8", doc="i8", datainfo=r#"Matrix(elementtype="<i8")"#, readonly=true)]
+#[param(name="u8", doc="u8", datainfo=r#"Matrix(elementtype="<u8")"#, readonly=true)]
+#[param(name="f4", doc="f4", datainfo=r#"Matrix(elementtype="<f4")"#, readonly=true)]
+#[param(name="f8", doc="f8", datainfo=r#"Matrix(elementtype="<f8")"#, readonly=true)]
+struct Elements {
+    internals: ModInternals,
+    params: ElementsParams,
+}
+
+impl_module!(Elements);
+
+macro_rules! read_empty {
+    ($($method:ident),*) => {
+        $( fn $method(&mut self) -> Result<MatrixData> { Ok(MatrixData::default()) } )*
+    };
+}
+
+impl ElementsHandlers for Elements {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    read_empty!(read_i1, read_u1, read_i2, read_u2, read_i4, read_u4, read_i8, read_u8,
+                read_f4, read_f8);
+}
+
+#[test]
+fn derive_element_types() {
+    // the derive has its own table of element types, which must match
+    let mut derived = Elements::class_info().params.iter().filter(|p| p.name != "status")
+        .map(|p| {
+            assert_eq!(p.datainfo["elementtype"], json!(format!("<{}", p.name)));
+            p.name
+        }).collect::<Vec<_>>();
+    let mut codes = ElementType::ALL.iter().map(|e| e.code()).collect::<Vec<_>>();
+    derived.sort_unstable();
+    codes.sort_unstable();
+    assert_eq!(derived, codes);
+}
@@ -0,0 +1,129 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for the initialization and persistence of parameter values.
+
+use std::sync::Arc;
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::server::ModRepReceiver;
+use secop_core::state::StateStore;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="value", doc="current value", datainfo="Double()", readonly=true)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="target", doc="target value", datainfo="Double()", readonly=false,
+        persistent=true)]
+#[param(name="speed", doc="speed", datainfo="Double(min=0.0)", readonly=false,
+        swonly=true, default="1.0", persistent=true)]
+#[param(name="mode", doc="mode", datainfo="Int()", readonly=false,
+        swonly=true, default="0")]
+struct Motor {
+    internals: ModInternals,
+    params: MotorParams,
+    target: f64,
+}
+
+impl_module!(Motor, target: 0.0);
+
+impl MotorHandlers for Motor {
+    fn read_value(&mut self) -> Result<f64> { Ok(self.target) }
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_target(&mut self) -> Result<f64> { Ok(self.target) }
+    fn write_target(&mut self, value: f64) -> Result<()> {
+        self.target = value;
+        Ok(())
+    }
+    fn update_speed(&mut self, _: f64) -> Result<()> { Ok(()) }
+    fn update_mode(&mut self, _: i64) -> Result<()> { Ok(()) }
+}
+
+/// Create and initialize the module with the given parameters in the config.
+fn init_motor(params: Value, state: &Arc<StateStore>) -> (Motor, ModRepReceiver) {
+    let config = common::config("Motor", params);
+    let (internals, _, rep_receiver) = common::internals_with_state("motor", config,
+                                                                    Arc::clone(state));
+    (common::init(internals), rep_receiver)
+}
+
+fn value(motor: &mut Motor, param: &str) -> Value {
+    motor.read(param).unwrap()[0].clone()
+}
+
+#[test]
+fn default_and_config() {
+    let state = Arc::new(StateStore::in_memory());
+    let (mut motor, _rep) = init_motor(json!({}), &state);
+    assert_eq!(value(&mut motor, "speed"), json!(1.0));
+    assert_eq!(value(&mut motor, "target"), json!(0.0));
+
+    let (mut motor, _rep) = init_motor(json!({"speed": 2.0, "target": 3.0}), &state);
+    assert_eq!(value(&mut motor, "speed"), json!(2.0));
+    assert_eq!(value(&mut motor, "target"), json!(3.0));
+}
+
+#[test]
+fn persisted_before_config() {
+    let state = Arc::new(StateStore::in_memory());
+    state.set("motor", "speed", json!(4.0));
+    state.set("motor", "target", json!(5.0));
+    let (mut motor, _rep) = init_motor(json!({"speed": 2.0, "target": 3.0}), &state);
+    assert_eq!(value(&mut motor, "speed"), json!(4.0));
+    assert_eq!(value(&mut motor, "target"), json!(5.0));
+    assert_eq!(motor.target, 5.0);
+}
+
+#[test]
+fn invalid_persisted_ignored() {
+    let state = Arc::new(StateStore::in_memory());
+    state.set("motor", "speed", json!(-1.0));
+    let (mut motor, _rep) = init_motor(json!({"speed": 2.0}), &state);
+    assert_eq!(value(&mut motor, "speed"), json!(2.0));
+}
+
+#[test]
+fn changes_are_persisted() {
+    let path = std::env::temp_dir().join(format!("secop-state-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let state = Arc::new(StateStore::open(&path));
+    let (mut motor, _rep) = init_motor(json!({}), &state);
+    motor.change("speed", json!(6.0)).unwrap();
+    motor.change("target", json!(7.0)).unwrap();
+    motor.change("mode", json!(1)).unwrap();
+    assert!(motor.change("speed", json!(-1.0)).is_err());
+
+    // a new server instance reads the state file
+    let state = Arc::new(StateStore::open(&path));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(state.get("motor", "speed"), Some(json!(6.0)));
+    assert_eq!(state.get("motor", "mode"), None);
+    let (mut motor, _rep) = init_motor(json!({"speed": 2.0}), &state);
+    assert_eq!(value(&mut motor, "speed"), json!(6.0));
+    assert_eq!(value(&mut motor, "target"), json!(7.0));
+    assert_eq!(value(&mut motor, "mode"), json!(0));
+}
@@ -0,0 +1,197 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for scheduling the polls of parameters.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::proto::{IncomingMsg, Msg};
+use secop_core::server::{ModRepReceiver, ReqSender, next_handler_id};
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="number of reads", datainfo="Int()", readonly=true)]
+#[param(name="aux", doc="number of reads", datainfo="Int()", readonly=true, poll="2",
+        busy_poll="0")]
+#[param(name="hidden", doc="number of reads", datainfo="Int()", readonly=true, poll="0")]
+#[param(name="busy", doc="if the module is busy", datainfo="Bool", readonly=false,
+        swonly=true, default="false")]
+#[param(name="stabilizing", doc="if the module is stabilizing", datainfo="Bool",
+        readonly=false, swonly=true, default="false")]
+#[param(name="pollinterval", doc="poll interval", datainfo="Double(min=0.1)", readonly=false,
+        swonly=true, default="1.0")]
+struct Poller {
+    internals: ModInternals,
+    params: PollerParams,
+    reads: [i64; 3],
+}
+
+impl_module!(Poller, reads: [0; 3]);
+
+impl PollerHandlers for Poller {
+    fn read_status(&mut self) -> Result<Status> {
+        Ok(if *self.params.busy {
+            (StatusConst::Busy, "busy".into())
+        } else if *self.params.stabilizing {
+            (StatusConst::Stabilizing, "stabilizing".into())
+        } else {
+            (StatusConst::Idle, "idle".into())
+        })
+    }
+    fn read_value(&mut self) -> Result<i64> { self.reads[0] += 1; Ok(self.reads[0]) }
+    fn read_aux(&mut self) -> Result<i64> { self.reads[1] += 1; Ok(self.reads[1]) }
+    fn read_hidden(&mut self) -> Result<i64> { self.reads[2] += 1; Ok(self.reads[2]) }
+}
+
+/// Uses the deprecated `polling` attribute.
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true, polling=true)]
+#[param(name="value", doc="value", datainfo="Int()", readonly=true, polling=-1)]
+#[param(name="target", doc="target", datainfo="Int()", readonly=false, polling=false)]
+struct Legacy {
+    internals: ModInternals,
+    params: LegacyParams,
+}
+
+impl_module!(Legacy);
+
+impl LegacyHandlers for Legacy {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<i64> { Ok(0) }
+    fn read_target(&mut self) -> Result<i64> { Ok(0) }
+    fn write_target(&mut self, _: i64) -> Result<()> { Ok(()) }
+}
+
+/// Start the module in its own thread.
+fn start() -> (Arc<VirtualClock>, ReqSender, ModRepReceiver) {
+    let clock = Arc::new(VirtualClock::new(0.));
+    let (internals, req_sender, rep_receiver) =
+        common::internals("poller", common::config("Poller", json!({})));
+    let internals = internals.with_clock(clock.clone());
+    thread::spawn(move || Poller::create(internals).unwrap().run());
+    (clock, req_sender, rep_receiver)
+}
+
+/// Change a parameter, waiting for the reply.
+fn change(req_sender: &ReqSender, rep_receiver: &ModRepReceiver, param: &str, value: Value) {
+    let hid = next_handler_id();
+    let msg = Msg::Change { module: "poller".into(), param: param.into(), value };
+    req_sender.send((hid, IncomingMsg::bare(msg))).unwrap();
+    while let Ok((rep_hid, msg)) = rep_receiver.recv_timeout(Duration::from_secs(5)) {
+        if rep_hid == Some(hid) {
+            assert!(matches!(msg, Msg::Changed { .. }), "unexpected reply {}", msg);
+            return;
+        }
+    }
+    panic!("no reply");
+}
+
+/// Advance the clock, and check which of the counters are polled, all at the
+/// new time, and that nothing else is polled.
+fn step(clock: &VirtualClock, rep_receiver: &ModRepReceiver, dt: f64, expected: &[&str]) {
+    clock.advance(dt);
+    let mut polled = vec![];
+    while polled.len() < expected.len() {
+        let (_, msg) = rep_receiver.recv_timeout(Duration::from_secs(5))
+                                   .expect("missing poll");
+        if let Msg::Update { param, data, .. } = msg {
+            if param != "status" {
+                assert_eq!(data[1]["t"], json!(clock.now()), "{} polled too late", param);
+                polled.push(param);
+            }
+        }
+    }
+    polled.sort();
+    assert_eq!(polled, expected, "at t = {}", clock.now());
+    while let Ok((_, msg)) = rep_receiver.recv_timeout(Duration::from_millis(100)) {
+        if let Msg::Update { param, .. } = msg {
+            assert_eq!(param, "status", "unexpected poll at t = {}", clock.now());
+        }
+    }
+}
+
+#[test]
+fn poll_intervals() {
+    let polled = Poller::polled_params().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(polled, ["status", "value", "aux"]);
+
+    let (clock, req, rep) = start();
+    // everything is read once on startup
+    step(&clock, &rep, 0., &["aux", "hidden", "value"]);
+
+    // the module's interval is one second, aux has its own
+    step(&clock, &rep, 1., &["value"]);
+    step(&clock, &rep, 1., &["aux", "value"]);
+
+    // a new module interval applies from the last poll
+    change(&req, &rep, "pollinterval", json!(2.5));
+    step(&clock, &rep, 1., &[]);
+    step(&clock, &rep, 1., &["aux"]);
+    step(&clock, &rep, 0.5, &["value"]);
+
+    // when the status shows busy, value is polled five times as often,
+    // while aux keeps its interval
+    change(&req, &rep, "busy", json!(true));
+    step(&clock, &rep, 2.5, &["aux", "value"]);
+    step(&clock, &rep, 0.5, &["value"]);
+    step(&clock, &rep, 0.5, &["value"]);
+    step(&clock, &rep, 1., &["aux", "value"]);
+
+    // and when idle again, at the normal interval
+    change(&req, &rep, "busy", json!(false));
+    step(&clock, &rep, 0.5, &[]);
+    step(&clock, &rep, 1.5, &["aux"]);
+    step(&clock, &rep, 0.5, &["value"]);
+}
+
+#[test]
+fn poll_while_stabilizing() {
+    let (clock, req, rep) = start();
+    step(&clock, &rep, 0., &["aux", "hidden", "value"]);
+
+    // stabilizing is a busy state as well, so value is polled five times
+    // as often as soon as the status shows it
+    change(&req, &rep, "stabilizing", json!(true));
+    step(&clock, &rep, 1., &["value"]);
+    step(&clock, &rep, 0.2, &["value"]);
+    step(&clock, &rep, 0.2, &["value"]);
+}
+
+#[test]
+fn deprecated_polling() {
+    let polled = Legacy::polled_params();
+    assert_eq!(polled.len(), 2);
+    assert_eq!(polled[0].0, "status");
+    assert_eq!((polled[0].1.normal, polled[0].1.busy), (None, None));
+    assert_eq!(polled[1].0, "value");
+    assert_eq!((polled[1].1.normal, polled[1].1.busy), (None, Some(0.0)));
+}
@@ -0,0 +1,91 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for value qualifiers reported by read methods.
+
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::module::ModParam;
+use secop_core::proto::Msg;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+use common::updates;
+
+#[test]
+fn modparam() {
+    let mut param = ModParam::new(Double::new());
+    assert_eq!(param.update(1.0, 10.0).unwrap(), (json!(1.0), 10.0, true));
+    assert_eq!(param.qualifiers(), json!({"t": 10.0}));
+
+    let quals = Qualifiers { timestamp: Some(5.0), error: Some(0.1) };
+    assert_eq!(param.update_qualified(1.0, quals, 10.0).unwrap(), (json!(1.0), 5.0, true));
+    assert_eq!(param.qualifiers(), json!({"t": 5.0, "e": 0.1}));
+    assert!(!param.update_qualified(1.0, quals, 10.0).unwrap().2);
+
+    assert!(param.update(1.0, 11.0).unwrap().2);
+    assert_eq!(param.qualifiers(), json!({"t": 11.0}));
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true, qualifiers=true)]
+#[param(name="gain", doc="gain", datainfo="Double()", readonly=false, swonly=true,
+        default="1.0")]
+struct Sensor {
+    internals: ModInternals,
+    params: SensorParams,
+    reading: (f64, Qualifiers),
+}
+
+impl_module!(Sensor, reading: (2.5, Qualifiers { timestamp: Some(100.0), error: Some(0.25) }));
+
+impl SensorHandlers for Sensor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<(f64, Qualifiers)> { Ok(self.reading) }
+}
+
+#[test]
+fn reported_qualifiers() {
+    let (mut sensor, rep) = common::create::<Sensor>("sensor", common::config("Sensor", json!({})));
+    assert!(updates(&rep).contains(&("value".into(), json!([2.5, {"t": 100.0, "e": 0.25}]))));
+    assert_eq!(sensor.read("value").unwrap(), json!([2.5, {"t": 100.0, "e": 0.25}]));
+
+    // no update if nothing changed, but a changed error is sent
+    sensor.read("value").unwrap();
+    assert!(updates(&rep).is_empty());
+    sensor.reading.1.error = Some(0.5);
+    sensor.read("value").unwrap();
+    assert_eq!(updates(&rep), [("value".into(), json!([2.5, {"t": 100.0, "e": 0.5}]))]);
+
+    // software-only parameters have only a timestamp
+    let reply = sensor.change("gain", json!(2.0)).unwrap();
+    assert_eq!(reply[1].as_object().unwrap().keys().collect::<Vec<_>>(), ["t"]);
+
+    let activation = sensor.activate_updates();
+    assert!(activation.iter().any(|msg| matches!(
+        msg, Msg::Update { param, data, .. }
+        if param == "value" && data == &json!([2.5, {"t": 100.0, "e": 0.5}]))));
+}
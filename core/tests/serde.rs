@@ -0,0 +1,184 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for using Rust types with serde support as data types.
+
+extern crate secop_core;
+
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_core::types::Scaled;
+use secop_derive::{ModuleBase, TypeInfo};
+
+#[macro_use]
+mod common;
+
+#[derive(TypeInfo, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum Gas {
+    He = 1,
+    N2 = 2,
+}
+
+impl Default for Gas {
+    fn default() -> Self { Gas::He }
+}
+
+#[derive(TypeInfo, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde_schema]
+struct GasFlow {
+    #[datainfo="GasType"]
+    gas: Gas,
+    #[datainfo="Double(min=0.0, max=100.0)"]
+    flow: f64,
+    #[datainfo="Scaled(scale=0.01, min=0, max=100_000)"]
+    pressure: f64,
+    #[datainfo="[Bool; 4]"]
+    valves: Vec<bool>,
+    #[datainfo="Str(maxchars=20)"]
+    comment: Option<String>,
+}
+
+#[derive(TypeInfo, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde_schema]
+struct Valve {
+    #[datainfo="Int(min=0)"]
+    #[serde(rename = "index")]
+    number: i64,
+    #[datainfo="Bool"]
+    open: bool,
+}
+
+fn flow() -> GasFlow {
+    GasFlow { gas: Gas::N2, flow: 12.5, pressure: 1.5, valves: vec![true, false],
+              comment: None }
+}
+
+#[test]
+fn description() {
+    assert_eq!(serde_json::to_value(Serde::<GasFlow>::new()).unwrap(), json!({
+        "type": "struct",
+        "members": {
+            "gas": {"type": "enum", "members": {"He": 1, "N2": 2}},
+            "flow": {"type": "double", "min": 0.0, "max": 100.0},
+            "pressure": {"type": "scaled", "scale": 0.01, "min": 0, "max": 100_000},
+            "valves": {"type": "array", "maxlen": 4, "members": {"type": "bool"}},
+            "comment": {"type": "string", "maxchars": 20},
+        },
+        "optional": ["comment"],
+    }));
+}
+
+#[test]
+fn conversion() {
+    let info = Serde::<GasFlow>::new();
+    let val = info.to_json(flow()).unwrap();
+    assert_eq!(val, json!({"gas": 2, "flow": 12.5, "pressure": 150, "valves": [true, false]}));
+    assert_eq!(info.from_json(&val).unwrap(), flow());
+
+    let with_comment = GasFlow { comment: Some("purge".into()), ..flow() };
+    let val = info.to_json(with_comment.clone()).unwrap();
+    assert_eq!(val["comment"], "purge");
+    assert_eq!(info.from_json(&val).unwrap(), with_comment);
+
+    // enum members can also be given by name
+    let val = json!({"gas": "He", "flow": 0, "pressure": 0, "valves": []});
+    assert_eq!(info.from_json(&val).unwrap().gas, Gas::He);
+}
+
+#[test]
+fn validation() {
+    let info = Serde::<GasFlow>::new();
+    assert!(info.to_json(GasFlow { flow: 101.0, ..flow() }).is_err());
+    assert!(info.to_json(GasFlow { valves: vec![true; 5], ..flow() }).is_err());
+    assert!(info.to_json(GasFlow { comment: Some("x".repeat(21)), ..flow() }).is_err());
+
+    let valid = json!({"gas": 1, "flow": 1.0, "pressure": 100, "valves": []});
+    assert!(info.from_json(&valid).is_ok());
+    for (key, value) in [("gas", json!(3)), ("flow", json!(-1.0)), ("pressure", json!(1.5)),
+                         ("valves", json!([1])), ("comment", json!(5)), ("other", json!(1))] {
+        let mut invalid = valid.clone();
+        invalid[key] = value;
+        assert!(info.from_json(&invalid).is_err(), "{} accepted", invalid);
+    }
+    let err = info.from_json(&json!({"gas": 1})).unwrap_err().to_string();
+    assert!(err.contains("missing flow in object"), "{}", err);
+}
+
+#[test]
+fn schema_mismatch() {
+    // the derived schema follows serde's renames
+    let info = Serde::<Valve>::new();
+    assert_eq!(serde_json::to_value(&info).unwrap()["members"]["index"]["type"], "int");
+    let valve = Valve { number: 2, open: true };
+    assert_eq!(info.to_json(valve.clone()).unwrap(), json!({"index": 2, "open": true}));
+    assert_eq!(info.from_json(&json!({"index": 2, "open": true})).unwrap(), valve);
+
+    // a schema that disagrees with the serialized shape rejects all values
+    let stale = Serde::<Valve>::with_schema(Struct::new().member("number", Int::new())
+                                                         .member("open", Bool));
+    assert!(stale.to_json(valve).is_err());
+    assert!(stale.from_json(&json!({"number": 2, "open": true})).is_err());
+}
+
+#[test]
+fn other_schemas() {
+    let info = Serde::<(String, Vec<u8>)>::with_schema(Tuple2(Str::new(), Blob::new().maxbytes(4)));
+    let val = info.to_json(("data".into(), vec![1, 2, 3])).unwrap();
+    assert_eq!(val, json!(["data", base64::encode([1, 2, 3])]));
+    assert_eq!(info.from_json(&val).unwrap(), ("data".into(), vec![1, 2, 3]));
+    assert!(info.to_json(("data".into(), vec![0; 5])).is_err());
+
+    let info = Serde::<Vec<Gas>>::with_schema(ArrayOf { minlen: 1, maxlen: 2, members: GasType });
+    assert_eq!(info.to_json(vec![Gas::He, Gas::N2]).unwrap(), json!([1, 2]));
+    assert_eq!(info.from_json(&json!([2])).unwrap(), vec![Gas::N2]);
+    assert!(info.to_json(vec![]).is_err());
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="setpoint", doc="gas flow setpoint", datainfo="Serde::<GasFlow>()",
+        readonly=false, swonly=true, default="GasFlow::default()")]
+struct FlowController {
+    internals: ModInternals,
+    params: FlowControllerParams,
+}
+
+impl_module!(FlowController);
+
+impl FlowControllerHandlers for FlowController {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn update_setpoint(&mut self, _: GasFlow) -> Result<()> { Ok(()) }
+}
+
+#[test]
+fn module_param() {
+    let info = FlowController::class_info();
+    let setpoint = info.params.iter().find(|p| p.name == "setpoint").unwrap();
+    assert_eq!(setpoint.datainfo["type"], "struct");
+    assert!((setpoint.check)(&json!({"gas": "N2", "flow": 1, "pressure": 0,
+                                      "valves": []})).is_ok());
+    assert!((setpoint.check)(&json!({"gas": "Ar", "flow": 1, "pressure": 0,
+                                      "valves": []})).is_err());
+}
@@ -0,0 +1,178 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for detecting that the target has been reached.
+
+use std::sync::Arc;
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::server::ModRepReceiver;
+use secop_core::stabilize::Stabilizer;
+use secop_derive::ModuleBase;
+
+#[macro_use]
+mod common;
+
+fn status(stab: &Stabilizer) -> StatusConst {
+    stab.status().0
+}
+
+#[test]
+fn reach_target() {
+    let mut stab = Stabilizer::new(0.5, 10., 0.);
+    assert_eq!(status(&stab), StatusConst::Idle);
+    stab.start(5., 0.);
+    assert_eq!(status(&stab), StatusConst::Busy);
+    stab.update(3., 1.);
+    assert_eq!(status(&stab), StatusConst::Busy);
+    stab.update(4.6, 2.);
+    assert_eq!(status(&stab), StatusConst::Stabilizing);
+    // leaving the tolerance band restarts the settling time
+    stab.update(5.6, 3.);
+    assert_eq!(status(&stab), StatusConst::Busy);
+    stab.update(5.2, 4.);
+    stab.update(5.1, 13.);
+    assert_eq!(status(&stab), StatusConst::Stabilizing);
+    assert!(stab.is_active());
+    stab.update(5.0, 14.);
+    assert_eq!(stab.status(), (StatusConst::Idle, "target reached".into()));
+    assert!(!stab.is_active());
+    // further values don't matter
+    stab.update(10., 15.);
+    assert_eq!(status(&stab), StatusConst::Idle);
+}
+
+#[test]
+fn timeout() {
+    let mut stab = Stabilizer::new(0.5, 10., 20.);
+    stab.start(5., 0.);
+    stab.update(4.8, 15.);
+    assert_eq!(status(&stab), StatusConst::Stabilizing);
+    stab.update(4.8, 21.);
+    assert_eq!(status(&stab), StatusConst::Warn);
+    // a new target starts over
+    stab.start(4.8, 22.);
+    stab.update(4.8, 22.);
+    assert_eq!(status(&stab), StatusConst::Stabilizing);
+    stab.stop();
+    assert_eq!(status(&stab), StatusConst::Idle);
+}
+
+#[derive(ModuleBase)]
+#[stabilizer]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="temperature", datainfo="Double()", readonly=true)]
+#[param(name="target", doc="setpoint", datainfo="Double()", readonly=false, default="0.0")]
+#[param(name="tolerance", doc="tolerance", datainfo="Double(min=0.0)", readonly=false,
+        swonly=true, default="0.5")]
+#[param(name="settle_time", doc="settling time", datainfo="Double(min=0.0)", readonly=false,
+        swonly=true, default="10.0")]
+#[param(name="timeout", doc="timeout", datainfo="Double(min=0.0)", readonly=false,
+        swonly=true, default="0.0")]
+struct Heater {
+    internals: ModInternals,
+    params: HeaterParams,
+    value: f64,
+    target: f64,
+}
+
+impl_module!(Heater, value: 0.0, target: 0.0);
+
+impl HeaterHandlers for Heater {
+    fn read_status(&mut self) -> Result<Status> { Ok(self.internals.stabilizer().status()) }
+    fn read_value(&mut self) -> Result<f64> { Ok(self.value) }
+    fn read_target(&mut self) -> Result<f64> { Ok(self.target) }
+    fn write_target(&mut self, value: f64) -> Result<()> {
+        self.target = value;
+        Ok(())
+    }
+}
+
+fn create() -> (Heater, Arc<VirtualClock>, ModRepReceiver) {
+    let clock = Arc::new(VirtualClock::new(0.));
+    let (internals, _, rep) = common::internals("heater", common::config("Heater", json!({})));
+    let heater = common::init(internals.with_clock(clock.clone()));
+    rep.try_iter().for_each(drop);
+    (heater, clock, rep)
+}
+
+/// Set the value, read it, and return the status updates sent meanwhile.
+fn read_value(heater: &mut Heater, rep: &ModRepReceiver, value: f64) -> Vec<Value> {
+    heater.value = value;
+    heater.read("value").unwrap();
+    common::updates(rep).into_iter().filter(|(param, _)| param == "status")
+                        .map(|(_, data)| data[0].clone()).collect()
+}
+
+#[test]
+fn status_updates() {
+    let (mut heater, clock, rep) = create();
+    heater.change("target", json!(5.0)).unwrap();
+    assert_eq!(read_value(&mut heater, &rep, 3.0), [json!([300, "approaching target"])]);
+    assert_eq!(read_value(&mut heater, &rep, 4.8), [json!([380, "stabilizing"])]);
+    clock.advance(5.);
+    assert!(read_value(&mut heater, &rep, 4.9).is_empty());
+    clock.advance(5.);
+    assert_eq!(read_value(&mut heater, &rep, 5.0), [json!([100, "target reached"])]);
+    assert!(read_value(&mut heater, &rep, 7.0).is_empty());
+
+    heater.change("timeout", json!(20.0)).unwrap();
+    heater.change("target", json!(10.0)).unwrap();
+    assert_eq!(read_value(&mut heater, &rep, 9.0), [json!([300, "approaching target"])]);
+    clock.advance(21.);
+    assert_eq!(read_value(&mut heater, &rep, 9.0),
+               [json!([200, "target not reached within timeout"])]);
+}
+
+/// Has a `timeout` parameter with another meaning, and doesn't use the
+/// stabilizer.
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true)]
+#[param(name="target", doc="target", datainfo="Double()", readonly=false, default="0.0")]
+#[param(name="timeout", doc="communication timeout", datainfo="Double(min=0.0)",
+        readonly=false, swonly=true, default="2.0")]
+struct Comm {
+    internals: ModInternals,
+    params: CommParams,
+}
+
+impl_module!(Comm);
+
+impl CommHandlers for Comm {
+    fn read_status(&mut self) -> Result<Status> { Ok(self.internals.stabilizer().status()) }
+    fn read_value(&mut self) -> Result<f64> { Ok(0.0) }
+    fn read_target(&mut self) -> Result<f64> { Ok(0.0) }
+    fn write_target(&mut self, _: f64) -> Result<()> { Ok(()) }
+}
+
+#[test]
+fn without_stabilizer() {
+    let (mut comm, _rep) = common::create::<Comm>("comm", common::config("Comm", json!({})));
+    comm.change("timeout", json!(5.0)).unwrap();
+    assert_eq!(comm.internals.stabilizer().timeout, 0.0);
+    comm.change("target", json!(1.0)).unwrap();
+    assert_eq!(status(comm.internals.stabilizer()), StatusConst::Idle);
+}
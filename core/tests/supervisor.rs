@@ -0,0 +1,162 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for supervising and restarting failed modules.
+
+use std::sync::Arc;
+use std::time::Duration;
+use log::*;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::proto::{IncomingMsg, Msg};
+use secop_core::registry::Registry;
+use secop_core::server::{ModRepReceiver, ReqSender, next_handler_id};
+use secop_derive::ModuleBase;
+
+mod common;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="failures", doc="number of times setup fails", datainfo="Int(min=0)",
+        readonly=true, swonly=true, default="0")]
+struct Flaky {
+    internals: ModInternals,
+    params: FlakyParams,
+}
+
+impl Module for Flaky {
+    fn create(internals: ModInternals) -> Result<Self> {
+        Ok(Flaky { internals, params: Default::default() })
+    }
+    fn setup(&mut self) -> Result<()> {
+        if self.internals.restarts() < *self.params.failures as usize {
+            Err(Error::comm_failed("device not found"))
+        } else {
+            Ok(())
+        }
+    }
+    fn teardown(&mut self) {}
+}
+
+impl FlakyHandlers for Flaky {
+    fn read_status(&mut self) -> Result<Status> {
+        Ok((StatusConst::Idle, format!("{} restarts", self.internals.restarts())))
+    }
+}
+
+/// Start the module in its supervised thread, with the given number of
+/// failures and restart settings.
+fn start(failures: i64, restart: Value) -> (Arc<VirtualClock>, ReqSender, ModRepReceiver) {
+    let mut registry = Registry::new();
+    registry.register::<Flaky>();
+    let clock = Arc::new(VirtualClock::new(0.));
+    let mut config = common::config("Flaky", json!({"failures": failures}));
+    config["restart"] = restart;
+    let (internals, req_sender, rep_receiver) = common::internals("flaky", config);
+    registry.run_module(internals.with_clock(clock.clone())).unwrap();
+    (clock, req_sender, rep_receiver)
+}
+
+/// Return the text of the next status update sent by the module.
+fn next_status(rep_receiver: &ModRepReceiver) -> String {
+    while let Ok((_, msg)) = rep_receiver.recv_timeout(Duration::from_secs(5)) {
+        if let Msg::Update { param, data, .. } = msg {
+            if param == "status" {
+                return format!("{} {}", data[0][0], data[0][1].as_str().unwrap());
+            }
+        }
+    }
+    panic!("no status update");
+}
+
+/// Send a request to the module and return the reply.
+fn request(req_sender: &ReqSender, rep_receiver: &ModRepReceiver, msg: Msg) -> Msg {
+    let hid = next_handler_id();
+    req_sender.send((hid, IncomingMsg::bare(msg))).unwrap();
+    while let Ok((rep_hid, msg)) = rep_receiver.recv_timeout(Duration::from_secs(5)) {
+        if rep_hid == Some(hid) {
+            return msg;
+        }
+    }
+    panic!("no reply");
+}
+
+#[test]
+fn restart_with_backoff() {
+    let (clock, req, rep) = start(2, json!({"delay": 1.0, "factor": 2.0, "max_delay": 60.0}));
+    // every attempt first initializes the parameters, then fails in setup
+    assert_eq!(next_status(&rep), "100 0 restarts");
+    assert_eq!(next_status(&rep), "400 setup failed: CommunicationFailed: device not found \
+                                   (restart #1 pending)");
+
+    // while down, requests get an error reply, and the status is served
+    let read = |param: &str| Msg::Read { module: "flaky".into(), param: param.into() };
+    match request(&req, &rep, read("failures")) {
+        Msg::ErrMsg { class, .. } => assert_eq!(class, "IsError"),
+        msg => panic!("unexpected reply {}", msg),
+    }
+    match request(&req, &rep, read("status")) {
+        Msg::Update { data, .. } => assert_eq!(data[0][0], 400),
+        msg => panic!("unexpected reply {}", msg),
+    }
+
+    clock.advance(1.);
+    assert_eq!(next_status(&rep), "100 1 restarts");
+    assert_eq!(next_status(&rep), "400 setup failed: CommunicationFailed: device not found \
+                                   (restart #2 pending)");
+    // the delay has doubled
+    clock.advance(1.);
+    assert!(rep.recv_timeout(Duration::from_millis(200)).is_err());
+    clock.advance(1.);
+    assert_eq!(next_status(&rep), "100 2 restarts");
+    match request(&req, &rep, read("failures")) {
+        Msg::Update { data, .. } => assert_eq!(data[0], 2),
+        msg => panic!("unexpected reply {}", msg),
+    }
+}
+
+#[test]
+fn give_up() {
+    let (clock, req, rep) = start(5, json!({"delay": 1.0, "factor": 1.0, "max_delay": 60.0,
+                                            "max_restarts": 2}));
+    for restarts in 0..2 {
+        assert_eq!(next_status(&rep), format!("100 {} restarts", restarts));
+        assert_eq!(next_status(&rep), format!("400 setup failed: CommunicationFailed: device \
+                                               not found (restart #{} pending)", restarts + 1));
+        clock.advance(1.);
+    }
+    assert_eq!(next_status(&rep), "100 2 restarts");
+    assert_eq!(next_status(&rep), "400 setup failed: CommunicationFailed: device not found \
+                                   (gave up after 2 restarts)");
+
+    // no more attempts are made, but requests are still answered
+    clock.advance(100.);
+    assert!(rep.recv_timeout(Duration::from_millis(200)).is_err());
+    match request(&req, &rep, Msg::Read { module: "flaky".into(), param: "status".into() }) {
+        Msg::Update { data, .. } => assert_eq!(data[0][1], "setup failed: CommunicationFailed: \
+                                                            device not found (gave up after \
+                                                            2 restarts)"),
+        msg => panic!("unexpected reply {}", msg),
+    }
+}
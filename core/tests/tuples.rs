@@ -0,0 +1,135 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for tuples and tuple structs.
+
+extern crate secop_core;
+
+use log::*;
+use serde_json::json;
+
+use secop_core::prelude::*;
+use secop_derive::{ModuleBase, TypeInfo};
+
+#[macro_use]
+mod common;
+
+#[derive(TypeInfo, Clone, Debug, Default, PartialEq)]
+struct Range(#[datainfo="Double()"] f64, #[datainfo="Double()"] f64, #[datainfo="Str()"] String);
+
+#[derive(TypeInfo, Clone, Copy, Debug, Default, PartialEq)]
+struct Kelvin(#[datainfo="Double(min=0.0, unit=\"K\")"] f64);
+
+#[test]
+fn tuple_struct() {
+    assert_eq!(serde_json::to_value(RangeType).unwrap(),
+               json!({"type": "tuple", "members": [{"type": "double"}, {"type": "double"},
+                                                   {"type": "string", "maxchars": 1024}]}));
+    let range = Range(1.0, 2.0, "low".into());
+    let val = RangeType.to_json(range.clone()).unwrap();
+    assert_eq!(val, json!([1.0, 2.0, "low"]));
+    assert_eq!(RangeType.from_json(&val).unwrap(), range);
+    assert!(RangeType.from_json(&json!([1.0, 2.0])).is_err());
+    assert!(RangeType.from_json(&json!([1.0, 2.0, "low", 3])).is_err());
+    let err = RangeType.from_json(&json!([1.0, "x", "low"])).unwrap_err().to_string();
+    assert!(err.contains("in item 1"), "{}", err);
+}
+
+#[test]
+fn newtype() {
+    assert_eq!(serde_json::to_value(KelvinType).unwrap(),
+               json!({"type": "double", "min": 0.0, "unit": "K"}));
+    assert_eq!(KelvinType.to_json(Kelvin(4.2)).unwrap(), json!(4.2));
+    assert_eq!(KelvinType.from_json(&json!(300.0)).unwrap(), Kelvin(300.0));
+    assert!(KelvinType.from_json(&json!(-1.0)).is_err());
+    assert!(KelvinType.from_json(&json!([1.0])).is_err());
+}
+
+#[test]
+fn tuple_lengths() {
+    let single = Tuple1(Int::new());
+    assert_eq!(serde_json::to_value(&single).unwrap(),
+               json!({"type": "tuple", "members": [{"type": "int", "min": i64::MIN,
+                                                    "max": i64::MAX}]}));
+    assert_eq!(single.from_json(&json!([5])).unwrap(), (5,));
+    assert!(single.from_json(&json!(5)).is_err());
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="temperature", datainfo="KelvinType", readonly=true)]
+#[param(name="calpoint", doc="calibration point",
+        datainfo="(Double(), Double(), Double(), Str(), Int(), Int(), Double())",
+        readonly=false, swonly=true, default="(0., 0., 0., String::new(), 0, 0, 0.)")]
+#[param(name="range", doc="range", datainfo="RangeType", readonly=false, swonly=true,
+        default="Range(0., 1., \"default\".into())")]
+#[param(name="coeffs", doc="polynomial coefficients",
+        datainfo="(Double(), Double(), Double(), Double(), Double(), Double(), Double(), \
+                   Double(), Double(), Double(), Double(), Double(), Str())",
+        readonly=false, swonly=true)]
+struct Sensor {
+    internals: ModInternals,
+    params: SensorParams,
+}
+
+impl_module!(Sensor);
+
+type CalPoint = (f64, f64, f64, String, i64, i64, f64);
+
+impl SensorHandlers for Sensor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_value(&mut self) -> Result<Kelvin> { Ok(Kelvin(4.2)) }
+    fn update_calpoint(&mut self, _: CalPoint) -> Result<()> { Ok(()) }
+    fn update_range(&mut self, _: Range) -> Result<()> { Ok(()) }
+    fn update_coeffs(&mut self, _: SensorCoeffs) -> Result<()> { Ok(()) }
+}
+
+#[test]
+fn module_params() {
+    let info = Sensor::class_info();
+    let calpoint = info.params.iter().find(|p| p.name == "calpoint").unwrap();
+    assert_eq!(calpoint.datainfo["members"].as_array().unwrap().len(), 7);
+    assert!((calpoint.check)(&json!([1., 2., 3., "pt100", 4, 5, 6.])).is_ok());
+    assert!((calpoint.check)(&json!([1., 2., 3., "pt100", 4, 5])).is_err());
+    let range = info.params.iter().find(|p| p.name == "range").unwrap();
+    assert!((range.check)(&json!([0., 10., "high"])).is_ok());
+}
+
+#[test]
+fn long_tuple() {
+    let info = Sensor::class_info();
+    let coeffs = info.params.iter().find(|p| p.name == "coeffs").unwrap();
+    assert_eq!(coeffs.datainfo["type"], "tuple");
+    assert_eq!(coeffs.datainfo["members"].as_array().unwrap().len(), 13);
+    assert_eq!(coeffs.datainfo["members"][12]["type"], "string");
+    let val = json!([0., 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., "poly"]);
+    assert!((coeffs.check)(&val).is_ok());
+    assert!((coeffs.check)(&json!([0., 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11.])).is_err());
+    let err = (coeffs.check)(&json!([0., 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12]))
+        .unwrap_err().to_string();
+    assert!(err.contains("in item 12"), "{}", err);
+
+    let value: SensorCoeffs = (0., 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11.,
+                               "poly".to_string()).into();
+    assert_eq!(value.12, "poly");
+    assert!(value != SensorCoeffs::default());
+}
@@ -0,0 +1,45 @@
+use secop_core::prelude::*;
+
+struct Plain {
+    internals: ModInternals,
+    params: PlainParams,
+}
+
+#[secop::module]
+impl Plain {
+    fn read_value(&mut self) -> Result<f64> { Ok(0.0) }
+}
+
+struct Args {
+    internals: ModInternals,
+    params: ArgsParams,
+}
+
+#[secop::module(name="Args")]
+impl ArgsHandlers for Args {}
+
+struct Sensor {
+    internals: ModInternals,
+    params: SensorParams,
+}
+
+#[secop::module]
+#[secop::command(argtype="Null", restype="Null")]
+impl SensorHandlers for Sensor {
+    /// value
+    #[secop::param(datainfo="Double()")]
+    fn write_value(&mut self, _: f64) -> Result<()> { Ok(()) }
+
+    /// stop
+    #[secop::param(datainfo="Double()")]
+    fn do_stop(&mut self, _: ()) -> Result<()> { Ok(()) }
+
+    /// status
+    #[secop::command(argtype="Null", restype="Null")]
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "".into())) }
+}
+
+#[secop::param(datainfo="Double()")]
+fn read_value() {}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use secop_core::prelude::*;
+use secop_derive::ModuleBase;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType")]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true, unknown=1)]
+#[command(name="stop", doc="stop", argtype="Null")]
+#[param(name="target" doc="target")]
+struct Motor {
+    internals: ModInternals,
+    params: MotorParams,
+}
+
+fn main() {}
@@ -0,0 +1,38 @@
+use secop_core::prelude::*;
+use secop_derive::ModuleBase;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="mode", doc="mode", datainfo="Enum(on=1, off=1)", readonly=false)]
+struct EnumValues {
+    internals: ModInternals,
+    params: EnumValuesParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double(", readonly=true)]
+struct Unparseable {
+    internals: ModInternals,
+    params: UnparseableParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", readonly=true,
+        datainfo="()")]
+struct EmptyTuple {
+    internals: ModInternals,
+    params: EmptyTupleParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo=r#"Matrix(elementtype="|f4", maxlen=[4])"#,
+        readonly=true)]
+struct BadMatrix {
+    internals: ModInternals,
+    params: BadMatrixParams,
+}
+
+fn main() {}
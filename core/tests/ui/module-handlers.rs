@@ -0,0 +1,28 @@
+use log::*;
+use secop_core::prelude::*;
+use secop_derive::ModuleBase;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true)]
+#[param(name="target", doc="target", datainfo="Double()", readonly=false)]
+struct Motor {
+    internals: ModInternals,
+    params: MotorParams,
+}
+
+impl Module for Motor {
+    fn create(internals: ModInternals) -> Result<Self> {
+        Ok(Motor { internals, params: Default::default() })
+    }
+    fn setup(&mut self) -> Result<()> { Ok(()) }
+    fn teardown(&mut self) {}
+}
+
+impl MotorHandlers for Motor {
+    fn read_status(&mut self) -> Result<Status> { Ok((StatusConst::Idle, "idle".into())) }
+    fn read_target(&mut self) -> Result<f64> { Ok(0.0) }
+    fn write_target(&mut self, value: f64) -> Result<f64> { Ok(value) }
+}
+
+fn main() {}
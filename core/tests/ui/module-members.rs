@@ -0,0 +1,13 @@
+use secop_core::prelude::*;
+use secop_derive::ModuleBase;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+struct Motor {
+    internals: ModInternals,
+}
+
+#[derive(ModuleBase)]
+struct Tuple(ModInternals);
+
+fn main() {}
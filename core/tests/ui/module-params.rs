@@ -0,0 +1,53 @@
+use secop_core::prelude::*;
+use secop_derive::ModuleBase;
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="speed", doc="speed", datainfo="Double()", readonly=false, swonly=true)]
+struct Swonly {
+    internals: ModInternals,
+    params: SwonlyParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true, persistent=true)]
+struct Persistent {
+    internals: ModInternals,
+    params: PersistentParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="Status", doc="status", datainfo="StatusType", readonly=true)]
+struct Duplicate {
+    internals: ModInternals,
+    params: DuplicateParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="serial", doc="serial", datainfo="Str()", readonly=false, constant=true,
+        default="String::new()")]
+struct Constant {
+    internals: ModInternals,
+    params: ConstantParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Str()", readonly=true, history="10")]
+struct History {
+    internals: ModInternals,
+    params: HistoryParams,
+}
+
+#[derive(ModuleBase)]
+#[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
+#[param(name="value", doc="value", datainfo="Double()", readonly=true, polling=5)]
+struct Polling {
+    internals: ModInternals,
+    params: PollingParams,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use secop_derive::TypeInfo;
+
+#[derive(TypeInfo, Clone)]
+enum Setting {
+    Off,
+    Value(f64),
+    Other { x: i64 },
+}
+
+const TWO: isize = 2;
+
+#[derive(TypeInfo, Clone, Copy)]
+enum Mode {
+    Off = -1,
+    On,
+    Fixed = TWO,
+}
+
+fn main() {}
@@ -0,0 +1,36 @@
+extern crate secop_core;
+
+use serde_derive::{Deserialize, Serialize};
+use secop_core::prelude::*;
+use secop_derive::TypeInfo;
+
+// Uses of Serde with the type don't cause further errors.
+#[derive(TypeInfo, Serialize, Deserialize)]
+#[serde_schema]
+struct Flat {
+    #[datainfo="Double()"]
+    #[serde(flatten)]
+    inner: std::collections::HashMap<String, f64>,
+    count: i64,
+}
+
+#[derive(TypeInfo, Serialize, Deserialize)]
+#[serde_schema]
+#[serde(rename_all = "camelCase")]
+struct Camel {
+    #[datainfo="Int()"]
+    max_count: i64,
+}
+
+#[derive(TypeInfo, Clone, Copy, Serialize, Deserialize)]
+#[serde_schema]
+enum Mode {
+    A,
+    B,
+}
+
+fn main() {
+    let _ = Serde::<Flat>::new();
+    let _ = Serde::<Camel>::new();
+    let _ = Serde::<Mode>::new();
+}
@@ -0,0 +1,15 @@
+use secop_derive::TypeInfo;
+
+#[derive(TypeInfo, Clone)]
+struct Point {
+    x: f64,
+    #[datainfo(Double)]
+    y: f64,
+    #[datainfo="Double(min=)"]
+    z: f64,
+}
+
+#[derive(TypeInfo, Clone)]
+struct Pair(#[datainfo="Double()"] f64, i64);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use secop_derive::TypeInfo;
+
+#[derive(TypeInfo, Clone, Copy)]
+union Value {
+    int: i64,
+    float: f64,
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+extern crate secop_core;
+
+use secop_core::prelude::*;
+use secop_derive::TypeInfo;
+
+// Uses of the metatype don't cause further errors.
+#[derive(TypeInfo, Clone, Default, PartialEq)]
+struct Calib {
+    #[datainfo="Double(min=)"]
+    offset: f64,
+    slope: f64,
+    #[datainfo="Double()"]
+    scale: f64,
+}
+
+fn describe(info: CalibType) -> serde_json::Value {
+    serde_json::to_value(&info).unwrap()
+}
+
+fn main() {
+    let _ = describe(CalibType);
+    let _ = CalibType.to_json(Calib::default());
+}
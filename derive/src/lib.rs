@@ -35,28 +35,281 @@
 //! * `TypeInfo` can be derived for enums and structs, and provides a type-
 //!   safe way to declare parameters and commands with enum and struct
 //!   datatypes.
+//!
+//! Alternatively to the `ModuleBase` derive, the `#[secop::module]` attribute
+//! on the module's handler impl block generates the same implementation from
+//! `#[secop::param]` and `#[secop::command]` attributes on the methods.
 
 mod module;
 mod typeinfo;
 
+use std::collections::HashSet;
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote};
 use syn::{Error, Expr};
 use synstructure::decl_derive;
 
-decl_derive!([ModuleBase, attributes(param, command)] => crate::module::derive_module);
-decl_derive!([TypeInfo, attributes(datainfo)] => crate::typeinfo::derive_typeinfo);
+decl_derive!([ModuleBase, attributes(param, command, property, stabilizer)] => crate::module::derive_module);
+
+#[proc_macro_derive(TypeInfo, attributes(datainfo, serde_schema))]
+pub fn derive_typeinfo(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    // synstructure would reject unions with a less helpful message
+    if let syn::Data::Union(u) = &input.data {
+        return Error::new(u.union_token.span, "TypeInfo cannot be derived for unions")
+            .to_compile_error().into();
+    }
+    match synstructure::Structure::try_new(&input) {
+        Ok(s) => crate::typeinfo::derive_typeinfo(s).into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Generate the module implementation from the handler impl block.
+///
+/// This is an alternative to `#[derive(ModuleBase)]`, see there for details.
+/// Re-exported as `secop_core::secop::module`, and named differently here to
+/// not clash with the `ModuleBase` helper attributes.
+#[proc_macro_attribute]
+pub fn secop_module(args: proc_macro::TokenStream, item: proc_macro::TokenStream)
+                    -> proc_macro::TokenStream {
+    crate::module::module_attribute(args.into(), item.into()).into()
+}
+
+/// Declare a parameter on one of its handler methods.
+///
+/// Only valid within a `#[secop::module]` impl block.
+#[proc_macro_attribute]
+pub fn secop_param(_: proc_macro::TokenStream, item: proc_macro::TokenStream)
+                   -> proc_macro::TokenStream {
+    misplaced("param", item)
+}
+
+/// Declare a command on its handler method.
+///
+/// Only valid within a `#[secop::module]` impl block.
+#[proc_macro_attribute]
+pub fn secop_command(_: proc_macro::TokenStream, item: proc_macro::TokenStream)
+                     -> proc_macro::TokenStream {
+    misplaced("command", item)
+}
+
+/// Declare a module property on the handler impl block.
+///
+/// Only valid after `#[secop::module]` on the impl block.
+#[proc_macro_attribute]
+pub fn secop_property(_: proc_macro::TokenStream, item: proc_macro::TokenStream)
+                      -> proc_macro::TokenStream {
+    misplaced("property", item)
+}
+
+/// The attributes are consumed by `#[secop::module]`, so if they are expanded
+/// on their own, they are in the wrong place.
+#[proc_macro_attribute]
+pub fn secop_stabilizer(_: proc_macro::TokenStream, item: proc_macro::TokenStream)
+                        -> proc_macro::TokenStream {
+    misplaced("stabilizer", item)
+}
+
+fn misplaced(kind: &str, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut result = Error::new(Span::call_site(),
+                                format!("#[secop::{}] can only be used within an impl \
+                                         block with #[secop::module]", kind)).to_compile_error();
+    result.extend(TokenStream::from(item));
+    result.into()
+}
 
 
 // Common helpers
 
+/// Collects errors, so that all of them can be reported at once.
+#[derive(Default)]
+struct Errors(Option<Error>);
+
+impl Errors {
+    fn push(&mut self, error: Error) {
+        match &mut self.0 {
+            Some(e) => e.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        self.0.map_or(Ok(()), Err)
+    }
+}
+
+/// Number of members up to which tuple types exist in secop_core::types.
+/// Their values are Rust tuples, for which the standard library implements
+/// `Clone`, `PartialEq` and `Default` only up to 12 members, so longer inline
+/// tuples get generated types, see `TupleTypes`.
+const MAX_TUPLE_LEN: usize = 12;
+
+/// Collects the types generated for inline tuples with more than
+/// `MAX_TUPLE_LEN` members.  The value of such a tuple is a tuple struct named
+/// after the parameter, command or member it belongs to, with a metatype
+/// named `<Name>Type`, just like a tuple struct deriving `TypeInfo`.
+pub(crate) struct TupleTypes {
+    krate: TokenStream,
+    vis: syn::Visibility,
+    items: Vec<TokenStream>,
+}
+
+impl TupleTypes {
+    /// `krate` is the path under which secop_core is reachable from the
+    /// generated code.
+    pub(crate) fn new(krate: TokenStream, vis: &syn::Visibility) -> Self {
+        Self { krate, vis: vis.clone(), items: vec![] }
+    }
+
+    pub(crate) fn into_items(self) -> Vec<TokenStream> {
+        self.items
+    }
+
+    /// Generate the value and metatype for a long tuple, and return the
+    /// metatype's name.
+    fn generate(&mut self, name: &str, members_t: &[TokenStream]) -> syn::Ident {
+        let krate = &self.krate;
+        let vis = &self.vis;
+        let repr_name = format_ident!("{}", name);
+        let struct_name = format_ident!("{}Type", name);
+        let len = members_t.len();
+        let indices = (0..len).map(syn::Index::from).collect::<Vec<_>>();
+        let items = (0..len).map(|i| format!("in item {}", i)).collect::<Vec<_>>();
+        let error = format!("expected array with {} elements", len);
+        let doc = format!("Value of the tuple described by `{}`.", struct_name);
+        let reprs = members_t.iter().map(
+            |t| quote!(<#t as #krate::types::TypeInfo>::Repr)).collect::<Vec<_>>();
+        let const_name = format_ident!("_DERIVE_TypeInfo_{}", name);
+        self.items.push(quote! {
+            #[doc = #doc]
+            #[derive(Clone, Default, PartialEq)]
+            #vis struct #repr_name( #( pub #reprs ),* );
+
+            #[derive(Clone)]
+            #vis struct #struct_name( #( pub #members_t ),* );
+
+            #[allow(non_upper_case_globals)]
+            const #const_name: () = {
+                use serde::ser::{Serialize, Serializer, SerializeMap};
+                use serde_json::{json, Value};
+                use #krate::errors::Error;
+                use #krate::types::TypeInfo;
+
+                impl From<( #( #reprs, )* )> for #repr_name {
+                    fn from(val: ( #( #reprs, )* )) -> Self {
+                        #repr_name( #( val.#indices ),* )
+                    }
+                }
+
+                impl Serialize for #struct_name {
+                    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> where S: Serializer
+                    {
+                        let members = [#( serde_json::to_value(&self.#indices).unwrap() ),*];
+                        let mut map = serializer.serialize_map(Some(2))?;
+                        map.serialize_entry("type", "tuple")?;
+                        map.serialize_entry("members", &members)?;
+                        map.end()
+                    }
+                }
+
+                impl TypeInfo for #struct_name {
+                    type Repr = #repr_name;
+
+                    fn to_json(&self, val: Self::Repr) -> std::result::Result<Value, Error> {
+                        Ok(json!([ #(
+                            self.#indices.to_json(val.#indices).map_err(|e| e.amend(#items))?
+                        ),* ]))
+                    }
+
+                    fn from_json(&self, val: &Value) -> std::result::Result<Self::Repr, Error> {
+                        match val.as_array() {
+                            Some(arr) if arr.len() == #len => Ok(#repr_name( #(
+                                self.#indices.from_json(&arr[#indices]).map_err(|e| e.amend(#items))?
+                            ),* )),
+                            _ => Err(Error::bad_value(#error))
+                        }
+                    }
+
+                    fn from_serde(&self, val: &Value) -> std::result::Result<Value, Error> {
+                        match val.as_array() {
+                            Some(arr) if arr.len() == #len => Ok(json!([ #(
+                                self.#indices.from_serde(&arr[#indices]).map_err(|e| e.amend(#items))?
+                            ),* ])),
+                            _ => Err(Error::bad_value(#error))
+                        }
+                    }
+
+                    fn to_serde(&self, val: &Value) -> std::result::Result<Value, Error> {
+                        match val.as_array() {
+                            Some(arr) if arr.len() == #len => Ok(json!([ #(
+                                self.#indices.to_serde(&arr[#indices]).map_err(|e| e.amend(#items))?
+                            ),* ])),
+                            _ => Err(Error::bad_value(#error))
+                        }
+                    }
+                }
+            };
+        });
+        struct_name
+    }
+}
+
+/// Convert a snake_case name into CamelCase, for naming generated types.
+pub(crate) fn camel_case(name: &str) -> String {
+    name.split('_').map(|part| {
+        let mut chars = part.chars();
+        chars.next().map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+    }).collect()
+}
+
 /// Translate a short "datainfo" attribute value into (datainfo type, datainfo value).
-fn translate_datainfo(span: Span, input: Expr) -> Result<(TokenStream, TokenStream), Error> {
+/// `name` is used to name types generated for long tuples.
+fn translate_datainfo(span: Span, input: Expr, name: &str, tuples: &mut TupleTypes)
+                      -> Result<(TokenStream, TokenStream), Error> {
     match input {
         // Simple names remain
         Expr::Path(p) => {
             Ok((quote!(#p), quote!(#p)))
         }
+        // Enum(a=1, b=2) -> enum with the given members
+        Expr::Call(c) if matches!(&*c.func, Expr::Path(p) if p.path.is_ident("Enum")) => {
+            let mut names = HashSet::new();
+            let mut values = HashSet::new();
+            let mut members = vec![];
+            for arg in c.args {
+                let (name, value) = match arg {
+                    Expr::Assign(a) => (a.left, a.right),
+                    _ => return Err(Error::new(span, "Enum(name=value, ...) expected")),
+                };
+                let name = match *name {
+                    Expr::Path(p) if p.path.get_ident().is_some() =>
+                        p.path.get_ident().unwrap().to_string(),
+                    Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => s.value(),
+                    _ => return Err(Error::new(span, "enum member name must be an \
+                                                      identifier or a string")),
+                };
+                let value = match parse_int(&value) {
+                    Some(Ok(v)) => v,
+                    Some(Err(e)) => return Err(Error::new(span, e)),
+                    None => return Err(Error::new(span, format!(
+                        "value of enum member {} must be an integer", name))),
+                };
+                if !names.insert(name.clone()) {
+                    return Err(Error::new(span, format!("duplicate enum member name {}", name)));
+                }
+                if !values.insert(value) {
+                    return Err(Error::new(span, format!(
+                        "enum member {} has the same value {} as another member", name, value)));
+                }
+                members.push(quote!( .member(#name, #value) ));
+            }
+            if members.is_empty() {
+                return Err(Error::new(span, "enum must have at least one member"));
+            }
+            Ok((quote!(Enum), quote!(Enum::new() #(#members)*)))
+        }
         // A(opt=1) -> struct syntax
         Expr::Call(c) => {
             let mut converted = vec![];
@@ -64,6 +317,10 @@ fn translate_datainfo(span: Span, input: Expr) -> Result<(TokenStream, TokenStre
                 if let Expr::Assign(a) = arg {
                     let key = a.left;
                     let val = a.right;
+                    if is_ident(&c.func, "Matrix") && is_ident(&key, "elementtype") {
+                        converted.push(translate_elementtype(span, &val)?);
+                        continue;
+                    }
                     converted.push(quote!( .#key(#val) ));
                 } else {
                     return Err(Error::new(span, "Type(opt=val, ...) expected"));
@@ -74,16 +331,26 @@ fn translate_datainfo(span: Span, input: Expr) -> Result<(TokenStream, TokenStre
         }
         // (A, B, C) -> tuple of subtypes
         Expr::Tuple(t) => {
+            if t.elems.is_empty() {
+                return Err(Error::new(span, "tuples must have at least one member"));
+            }
             let mut members = vec![];
             let mut members_t = vec![];
-            let tuple_n = format_ident!("Tuple{}", t.elems.len());
-            for elem in t.elems {
-                let (mem_t, mem) = translate_datainfo(span, elem)?;
+            let len = t.elems.len();
+            for (i, elem) in t.elems.into_iter().enumerate() {
+                let (mem_t, mem) = translate_datainfo(span, elem, &format!("{}{}", name, i),
+                                                      tuples)?;
                 members_t.push(mem_t);
                 members.push(mem);
             }
-            Ok((quote!(#tuple_n< #(#members_t),* >),
-                quote!(#tuple_n( #(#members),* ))))
+            if len <= MAX_TUPLE_LEN {
+                let tuple_n = format_ident!("Tuple{}", len);
+                Ok((quote!(#tuple_n< #(#members_t),* >),
+                    quote!(#tuple_n( #(#members),* ))))
+            } else {
+                let struct_name = tuples.generate(name, &members_t);
+                Ok((quote!(#struct_name), quote!(#struct_name( #(#members),* ))))
+            }
         }
         // [A; 5] or [A; 1..5] -> array
         Expr::Repeat(a) => {
@@ -98,7 +365,7 @@ fn translate_datainfo(span: Span, input: Expr) -> Result<(TokenStream, TokenStre
                 }
                 _ => return Err(Error::new(span, "[Type; N] or [Type; N..=M] expected")),
             };
-            let (sub_t, sub) = translate_datainfo(span, *a.expr)?;
+            let (sub_t, sub) = translate_datainfo(span, *a.expr, name, tuples)?;
             Ok((quote!(ArrayOf<#sub_t>),
                 quote!(ArrayOf { minlen: #min, maxlen: #max, members: #sub })))
         }
@@ -107,9 +374,55 @@ fn translate_datainfo(span: Span, input: Expr) -> Result<(TokenStream, TokenStre
     }
 }
 
-pub(crate) fn parse_datainfo(span: Span, input: &str) -> Result<(TokenStream, TokenStream), Error> {
+/// Return true if the expression is the given plain identifier.
+fn is_ident(expr: &Expr, name: &str) -> bool {
+    matches!(expr, Expr::Path(p) if p.path.is_ident(name))
+}
+
+/// Matrix element type codes and their `ElementType` variants.  This must
+/// match the table in secop_core::types, which is checked by a test there.
+const ELEMENT_TYPES: &[(&str, &str)] = &[
+    ("i1", "I8"), ("u1", "U8"), ("i2", "I16"), ("u2", "U16"), ("i4", "I32"),
+    ("u4", "U32"), ("i8", "I64"), ("u8", "U64"), ("f4", "F32"), ("f8", "F64"),
+];
+
+/// Check a matrix element type like `"<f4"` already at compile time, since
+/// `Matrix::elementtype` would fail only at runtime.
+fn translate_elementtype(span: Span, val: &Expr) -> Result<TokenStream, Error> {
+    let val = match val {
+        Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => s.value(),
+        _ => return Err(Error::new(span, "matrix element type must be a string")),
+    };
+    let invalid = || Error::new(span, format!("invalid matrix element type {:?}", val));
+    let (code, variant) = ELEMENT_TYPES.iter().find(|(code, _)| val.get(1..) == Some(code))
+                                                  .ok_or_else(invalid)?;
+    let order = match &val[..1] {
+        "<" => "Little",
+        ">" => "Big",
+        "|" if code.ends_with('1') => "Little",
+        _ => return Err(invalid()),
+    };
+    let variant = format_ident!("{}", variant);
+    let order = format_ident!("{}", order);
+    Ok(quote!( .element(secop_core::types::ElementType::#variant,
+                        secop_core::types::ByteOrder::#order) ))
+}
+
+/// Parse an integer literal, which may be negative.  Returns None if the
+/// expression is not an integer literal.
+pub(crate) fn parse_int(expr: &Expr) -> Option<Result<i64, Error>> {
+    match expr {
+        Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => Some(i.base10_parse()),
+        Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) =>
+            parse_int(expr).map(|r| r.map(|v| -v)),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_datainfo(span: Span, input: &str, name: &str, tuples: &mut TupleTypes)
+                             -> Result<(TokenStream, TokenStream), Error> {
     match syn::parse_str::<Expr>(input) {
-        Ok(d) => translate_datainfo(span, d),
+        Ok(d) => translate_datainfo(span, d, name, tuples),
         Err(e) => Err(Error::new(span, format!("invalid datainfo: {}", e))),
     }
 }
@@ -52,11 +52,66 @@
 //! }
 //! ```
 //!
+//! Tuples can be given inline as `datainfo="(Double(), Str())"`.  Their values
+//! are Rust tuples for up to 12 members.  Since the standard library implements
+//! `Clone`, `PartialEq` and `Default` only up to that length, longer tuples get
+//! a generated tuple struct named after the module and parameter, e.g.
+//! `MotorCalib` (with metatype `MotorCalibType`) for parameter `calib` of
+//! `Motor`, which can be created from a Rust tuple with `into()`.  For command
+//! arguments and results, the names end in `Arg` and `Result`.  The member
+//! values of such tuples must implement `Clone`, `PartialEq` and `Default`.
+//!
+//! Enums whose members are not known as a Rust type can be given inline as
+//! `datainfo="Enum(off=0, on=1)"`.  If the members are only known at runtime,
+//! assign a new `Enum` to `self.params.<name>.info` when creating the module.
+//! The members of such parameters can also be set in the config, which takes
+//! precedence, as `enums.<name> = ["A", "B"]` or `enums.<name> = {A = 1, B = 2}`.
+//!
+//! Writable parameters with the `persistent` flag keep their last value across
+//! restarts, if the node has a `datadir` configured.  The initial value of a
+//! parameter is taken from the first of: persisted value, config, default,
+//! and (for hardware parameters) the hardware.
+//!
+//! Parameters are polled at the module's `pollinterval` (and five times as
+//! often while the module is busy), unless they have their own intervals in
+//! seconds with `poll="..."` and `busy_poll="..."`.  `poll="0"` disables
+//! polling.  The deprecated `polling=true/false` (or `polling=1/0/-1`) is
+//! still accepted and mapped to these.
+//!
+//! With `#[stabilizer]` on the struct (or `#[secop::stabilizer]` on the impl
+//! block), the module's stabilizer follows the approach of `value` to a new
+//! `target`, and the software-only parameters `tolerance`, `settle_time` and
+//! `timeout` (if declared) configure it.  The `read_status` method can then
+//! return `self.internals.stabilizer().status()`.  Without it, these names
+//! are ordinary parameters.
+//!
+//! Parameters with `qualifiers=true` have a read method that returns
+//! `(value, Qualifiers)`, to report the error of the value or a timestamp
+//! from the hardware.  These are included in all data reports.
+//!
+//! Parameters that never change, like a serial number, can be declared with
+//! `constant=true` (and `readonly=true`).  Their value comes from the config or
+//! the default, and is published in the description instead of being polled
+//! and sent as updates.
+//!
+//! Numeric parameters can keep their past values with `history="N"`, or
+//! with `history.<name> = N` in the module's config.  Then the module gets a
+//! `get_history` command returning the values and their statistics over a
+//! given window.
+//!
+//! Module properties can be declared with `#[property(name="...", value="...")]`,
+//! where the value is given in JSON syntax.  Properties from the module's
+//! config take precedence.  If not given, `interface_classes` is determined
+//! from the presence of `value`, `status`, `target` and `stop`.
+//!
 //! You must afterwards also implement the `Module` trait, which contains all
-//! APIs that cannot be derived automatically, and inherent methods that
-//! implement the actual reading, writing, and execution.  These have very
-//! simple signatures since all data is in terms of Rust types, and has been
-//! validated against the SECoP type specification.  For the above example:
+//! APIs that cannot be derived automatically, and the `<Struct>Handlers` trait
+//! (also generated by the derive macro), whose methods implement the actual
+//! reading, writing, and execution.  These have very simple signatures since
+//! all data is in terms of Rust types, and has been validated against the SECoP
+//! type specification.  Parameters with `swonly` get an `update_` method instead
+//! of `read_`/`write_`, which is called after a change and does nothing by
+//! default.  For the above example:
 //!
 //! ```
 //! impl Module for Motor {
@@ -70,20 +125,58 @@
 //!
 //! // expected argument types here are determined by the `datatype` selected
 //! // in the param/command attribute above
-//! impl Motor {
-//!     // note, read_ can take &mut self or &self
+//! impl MotorHandlers for Motor {
 //!     fn read_value(&mut self) -> Result<f64> { ... }
 //!     fn write_target(&mut self, tgt: f64) -> Result<()> { ... }
 //!     fn do_stop(&mut self, arg: ()) -> Result<()> { ... }
 //! }
 //! ```
+//!
+//! Alternatively, parameters and commands can be declared directly on the
+//! handler methods, with `#[secop::module]` on the impl block instead of the
+//! derive on the struct.  The name is taken from the method, the description
+//! from its doc comment, and `readonly`/`swonly` from which methods exist.
+//! Parameters without handler methods and properties are declared after
+//! `#[secop::module]` on the impl block, and its doc comment is used for the
+//! class.  Since the attribute does not see the struct, the generated
+//! `<Struct>Params` struct and `<Struct>Handlers` trait are always private,
+//! while the derive gives them the visibility of the struct.  Both forms
+//! support generic module structs.  The example above then becomes:
+//!
+//! ```ignore
+//! #[secop::module]
+//! impl MotorHandlers for Motor {
+//!     /// status of the motor
+//!     #[secop::param(datainfo="StatusType")]
+//!     fn read_status(&mut self) -> Result<Status> { ... }
+//!
+//!     /// current position
+//!     #[secop::param(datainfo="Double()")]
+//!     fn read_value(&mut self) -> Result<f64> { ... }
+//!
+//!     /// target position
+//!     #[secop::param(datainfo="Double()")]
+//!     fn read_target(&mut self) -> Result<f64> { ... }
+//!     fn write_target(&mut self, tgt: f64) -> Result<()> { ... }
+//!
+//!     /// moving speed
+//!     #[secop::param(datainfo="Double(min=0.0)", default="1.0")]
+//!     fn read_speed(&mut self) -> Result<f64> { ... }
+//!     fn write_speed(&mut self, speed: f64) -> Result<()> { ... }
+//!
+//!     /// stop the motor
+//!     #[secop::command(argtype="Null", restype="Null")]
+//!     fn do_stop(&mut self, arg: ()) -> Result<()> { ... }
+//! }
+//! ```
 
 use std::collections::HashSet;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use syn::{Error, Expr, spanned::Spanned};
-use quote::{quote, quote_spanned, format_ident};
+use quote::{quote, format_ident};
 use darling::FromMeta;
 
+use crate::{Errors, TupleTypes, camel_case};
 
 /// All the possible properties of a parameter.
 ///
@@ -102,6 +195,11 @@ struct SecopParam {
     /// need to be propagated to hardware.
     #[darling(default)]
     swonly: bool,
+    /// If true, the parameter never changes.  It is published with its value
+    /// in the description, and is not polled and sends no updates.  Implies
+    /// swonly, so the value is taken from the config or the default.
+    #[darling(default)]
+    constant: bool,
     /// If true, the parameter must be given in the config file.
     /// (Not possible if readonly and !swonly).
     #[darling(default)]
@@ -110,11 +208,36 @@ struct SecopParam {
     /// Parameters with swonly set *must* have a default.
     #[darling(default)]
     default: Option<String>,
-    /// Poll interval, in multiples of the poll interval.
-    /// If negative, do not accelerate polling when module is busy.
+    /// If true, the parameter's value is the name of another module that
+    /// this module uses.
+    #[darling(default)]
+    module_ref: bool,
+    /// If true, the last value set is stored and used on the next startup,
+    /// with precedence over the config and the default.
+    #[darling(default)]
+    persistent: bool,
+    /// Poll interval in seconds, or 0 to disable polling.  If not given,
+    /// the module's `pollinterval` is used.
     /// Parameters with swonly set are not polled.
     #[darling(default)]
-    polling: Option<i64>,
+    poll: Option<f64>,
+    /// Poll interval in seconds while the module is busy, or 0 to keep the
+    /// normal interval.  If not given, it is 1/5 of the normal interval.
+    #[darling(default)]
+    busy_poll: Option<f64>,
+    /// Deprecated: poll interval as a multiple of the module's `pollinterval`,
+    /// negative to not poll faster while busy.  Only 0 (no polling), 1 and -1
+    /// can be expressed with `poll` and `busy_poll`, and are mapped to them.
+    #[darling(default)]
+    polling: Option<Polling>,
+    /// Number of past values to keep, for numeric parameters.  Can be
+    /// changed in the config.
+    #[darling(default)]
+    history: usize,
+    /// If true, the read method returns `Qualifiers` along with the value,
+    /// such as the error or a hardware timestamp.
+    #[darling(default)]
+    qualifiers: bool,
     /// The unit of the parameter's value.
     #[darling(default)]
     unit: String,
@@ -127,6 +250,27 @@ struct SecopParam {
     visibility: String,
 }
 
+/// Value of the deprecated `polling` attribute, which was an integer, but
+/// also accepts `true` and `false`.
+#[derive(Debug, Clone, Copy)]
+struct Polling(i64);
+
+impl FromMeta for Polling {
+    fn from_value(value: &syn::Lit) -> darling::Result<Self> {
+        match value {
+            syn::Lit::Bool(b) => Ok(Polling(i64::from(b.value))),
+            _ => i64::from_value(value).map(Polling),
+        }
+    }
+}
+
+/// Parameters whose update methods are implemented by `ModuleBase`.
+const BUILTIN_UPDATES: &[&str] = &["pollinterval"];
+
+/// Parameters that configure the stabilizer, if the module uses it.  Their
+/// update methods are also implemented by `ModuleBase`.
+const STABILIZER_UPDATES: &[&str] = &["tolerance", "settle_time", "timeout"];
+
 // Can't use the definition of core, since core depends on this crate.
 const VISIBILITIES: &[&str] = &["none", "user", "advanced", "expert"];
 fn default_visibility() -> String { "user".into() }
@@ -144,13 +288,66 @@ struct SecopCommand {
     visibility: String,
 }
 
+/// Representation of the #[property(...)] attribute.
+#[derive(FromMeta, Debug)]
+struct SecopProperty {
+    name: String,
+    /// Value in JSON syntax.
+    value: String,
+}
+
+/// Module properties that can be declared, apart from custom ones.
+const MODULE_PROPERTIES: &[&str] = &["implementation", "interface_classes", "features", "meaning"];
+
+
+/// Everything needed to generate a module's implementation, collected either
+/// from the attributes of the struct or from the handler impl block.
+struct ModuleSpec {
+    name: syn::Ident,
+    /// Generics of the module struct, and the struct type using them.
+    generics: syn::Generics,
+    self_ty: syn::Type,
+    vis: syn::Visibility,
+    doc: String,
+    params: Vec<(Span, SecopParam)>,
+    commands: Vec<(Span, SecopCommand)>,
+    properties: Vec<(Span, SecopProperty)>,
+    /// If true, the stabilizer follows value and target, and is configured
+    /// by the `tolerance`, `settle_time` and `timeout` parameters.
+    stabilizer: bool,
+    /// Errors found while collecting the above.  If there are any, only a
+    /// skeleton of the implementation is generated.
+    errors: TokenStream,
+}
+
+/// Parse an attribute's meta item.
+fn parse_meta(attr: &syn::Attribute) -> Result<syn::Meta, TokenStream> {
+    attr.parse_meta().map_err(|err| {
+        Error::new(err.span(), format!("invalid attribute: {}", err)).to_compile_error()
+    })
+}
 
 /// Parse an attribute (using darling) into the given struct representation.
 fn parse_attr<T: FromMeta>(attr: &syn::Attribute) -> Result<T, TokenStream> {
-    attr.parse_meta()
-        .map_err(|err| format!("invalid param attribute: {}", err))
-        .and_then(|meta| T::from_meta(&meta).map_err(|_| "could not parse this attribute".into()))
-        .map_err(|e| quote_spanned! { attr.span() => compile_error!(#e); })
+    let meta = parse_meta(attr)?;
+    T::from_meta(&meta).map_err(|err| err.with_span(&meta).write_errors())
+}
+
+/// Check that an attribute that acts as a flag has no arguments.
+fn parse_flag(attr: &syn::Attribute) -> Result<(), TokenStream> {
+    match parse_meta(attr)? {
+        syn::Meta::Path(_) => Ok(()),
+        meta => Err(Error::new(meta.span(), "attribute takes no arguments").to_compile_error()),
+    }
+}
+
+/// Join the doc comments in the given attributes.
+fn doc_comment(attrs: &[syn::Attribute]) -> String {
+    attrs.iter().filter_map(|attr| match attr.parse_meta() {
+        Ok(syn::Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Str(s), .. }))
+            if path.is_ident("doc") => Some(s.value().trim().to_string()),
+        _ => None
+    }).collect::<Vec<_>>().join("\n")
 }
 
 /// Use instead of panic!() to assign the error to a proper span, if possible.
@@ -161,37 +358,40 @@ macro_rules! try_ {
             Err(e) => return TokenStream::from(e.to_compile_error())
         }
     );
-    ($expr:expr, $($tt:tt)*) => (
-        match $expr {
-            Ok(v) => v,
-            Err(mut e) => {
-                e.combine(Error::new(e.span(), format!($($tt)*)));
-                return TokenStream::from(e.to_compile_error())
-            }
-        }
-    );
 }
 
 /// Main derive function for ModuleBase.
 pub fn derive_module(input: synstructure::Structure) -> TokenStream {
     let mut params = Vec::new();
     let mut commands = Vec::new();
+    let mut properties = Vec::new();
 
     let name = &input.ast().ident;
-    let vis = &input.ast().vis;
-    let param_struct_name = format_ident!("{}Params", name);
 
-    // Parse parameter and command attributes on the main struct.
+    // Parse parameter and command attributes on the main struct.  Errors
+    // are collected to report all of them at once.
+    let mut attr_errors = TokenStream::new();
+    let mut stabilizer = false;
     for attr in &input.ast().attrs {
         if attr.path.segments[0].ident == "param" {
             match parse_attr::<SecopParam>(attr) {
                 Ok(param) => params.push((attr.span(), param)),
-                Err(err) => return err
+                Err(err) => attr_errors.extend(err),
             }
         } else if attr.path.segments[0].ident == "command" {
             match parse_attr::<SecopCommand>(attr) {
                 Ok(cmd) => commands.push((attr.span(), cmd)),
-                Err(err) => return err
+                Err(err) => attr_errors.extend(err),
+            }
+        } else if attr.path.segments[0].ident == "property" {
+            match parse_attr::<SecopProperty>(attr) {
+                Ok(prop) => properties.push((attr.span(), prop)),
+                Err(err) => attr_errors.extend(err),
+            }
+        } else if attr.path.segments[0].ident == "stabilizer" {
+            match parse_flag(attr) {
+                Ok(()) => stabilizer = true,
+                Err(err) => attr_errors.extend(err),
             }
         }
     }
@@ -205,17 +405,224 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
                 if field.ident.as_ref().unwrap() == "internals" { has_internals = true; }
                 if field.ident.as_ref().unwrap() == "params" { has_params = true; }
             }
+            if !has_internals || !has_params {
+                attr_errors.extend(Error::new(
+                    input.ast().ident.span(),
+                    format!("struct {} must have \"internals: ModInternals\" and \
+                             \"params: {}Params\" members", name, name)).to_compile_error());
+            }
+        }
+        _ => attr_errors.extend(Error::new(input.ast().ident.span(),
+                                           "derive(ModuleBase) is only possible for a \
+                                            struct with named fields").to_compile_error()),
+    }
+
+    let generics = input.ast().generics.clone();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let self_ty = syn::parse_quote!(#name #ty_generics);
+    generate_module(ModuleSpec {
+        name: name.clone(),
+        generics,
+        self_ty,
+        vis: input.ast().vis.clone(),
+        doc: doc_comment(&input.ast().attrs),
+        params,
+        commands,
+        properties,
+        stabilizer,
+        errors: attr_errors,
+    })
+}
+
+/// If the attribute is one of `#[secop::<kind>]` (or plain `#[<kind>]`) with the
+/// given kinds, return the kind.
+fn secop_attr_kind(attr: &syn::Attribute, kinds: &[&'static str]) -> Option<&'static str> {
+    let segments = &attr.path.segments;
+    if segments.len() == 1 || (segments.len() == 2 && segments[0].ident == "secop") {
+        let last = &segments[segments.len() - 1].ident;
+        kinds.iter().find(|&kind| last == kind).copied()
+    } else {
+        None
+    }
+}
+
+/// Parse an attribute on a handler method, after adding the given items
+/// that are determined by the method.
+fn parse_method_attr<T: FromMeta>(attr: &syn::Attribute, added: Vec<syn::NestedMeta>)
+                                  -> Result<T, TokenStream> {
+    let mut list = match parse_meta(attr)? {
+        syn::Meta::List(list) => list,
+        syn::Meta::Path(path) => syn::MetaList { path, paren_token: Default::default(),
+                                                 nested: Default::default() },
+        syn::Meta::NameValue(nv) => return Err(Error::new(
+            nv.span(), "invalid attribute, expected a list of items").to_compile_error()),
+    };
+    for (i, item) in added.into_iter().enumerate() {
+        list.nested.insert(i, item);
+    }
+    let meta = syn::Meta::List(list);
+    T::from_meta(&meta).map_err(|err| err.with_span(&meta).write_errors())
+}
+
+/// Main function for the `#[secop::module]` attribute on the handler impl block.
+///
+/// Parameters and commands are declared with `#[secop::param(...)]` and
+/// `#[secop::command(...)]` on their methods.  Name, description and access
+/// mode are taken from the method name and doc comment, and the presence of
+/// other methods.
+pub fn module_attribute(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item: syn::ItemImpl = try_!(syn::parse2(item));
+    let mut attr_errors = TokenStream::new();
+    if !args.is_empty() {
+        attr_errors.extend(Error::new(args.span(), "#[secop::module] takes no arguments")
+                           .to_compile_error());
+    }
+
+    // Determine the module name from `impl <Name>Handlers for <Name>`.  If
+    // the trait is wrong, the skeleton is still generated for the struct.
+    let name = match &*item.self_ty {
+        syn::Type::Path(ty) if ty.qself.is_none() && ty.path.segments.len() == 1 =>
+            Some(ty.path.segments[0].ident.clone()),
+        _ => None,
+    };
+    let is_handlers = matches!((&item.trait_, &name), (Some((None, path, _)), Some(name))
+        if path.segments.last().map_or(false, |seg| seg.ident == format!("{}Handlers", name)));
+    let wrong_impl = Error::new(
+        item.span(), "#[secop::module] must be placed on an `impl <Name>Handlers for <Name>` block");
+    let name = match name {
+        Some(name) => name,
+        None => return wrong_impl.to_compile_error(),
+    };
+    if !is_handlers {
+        attr_errors.extend(wrong_impl.to_compile_error());
+    }
+
+    let mut params = Vec::new();
+    let mut commands = Vec::new();
+    let mut properties = Vec::new();
+
+    // Parameters without handler methods, and properties, are declared on
+    // the impl block itself.
+    let mut impl_attrs = vec![];
+    let mut stabilizer = false;
+    for attr in std::mem::take(&mut item.attrs) {
+        match secop_attr_kind(&attr, &["param", "command", "property", "stabilizer"]) {
+            Some("param") => match parse_attr::<SecopParam>(&attr) {
+                Ok(param) => params.push((attr.span(), param)),
+                Err(err) => attr_errors.extend(err),
+            },
+            Some("property") => match parse_attr::<SecopProperty>(&attr) {
+                Ok(prop) => properties.push((attr.span(), prop)),
+                Err(err) => attr_errors.extend(err),
+            },
+            Some("stabilizer") => match parse_flag(&attr) {
+                Ok(()) => stabilizer = true,
+                Err(err) => attr_errors.extend(err),
+            },
+            Some(_) => attr_errors.extend(Error::new(
+                attr.span(), "commands must be declared on their do_ method").to_compile_error()),
+            None => impl_attrs.push(attr),
+        }
+    }
+    item.attrs = impl_attrs;
+    let doc = doc_comment(&item.attrs);
+
+    // Collect the declarations from the methods.
+    let mut method_names = HashSet::new();
+    let mut declarations = vec![];
+    for impl_item in &mut item.items {
+        if let syn::ImplItem::Method(method) = impl_item {
+            method_names.insert(method.sig.ident.to_string());
+            let doc = doc_comment(&method.attrs);
+            let mut attrs = vec![];
+            for attr in std::mem::take(&mut method.attrs) {
+                match secop_attr_kind(&attr, &["param", "command"]) {
+                    Some(kind) => declarations.push((kind, attr, method.sig.ident.to_string(),
+                                                     doc.clone())),
+                    None => attrs.push(attr),
+                }
+            }
+            method.attrs = attrs;
+        }
+    }
+
+    for (kind, attr, method, doc) in declarations {
+        let span = attr.span();
+        let (prefix, name) = method.split_at(method.find('_').map_or(0, |i| i + 1));
+        let mut added: Vec<syn::NestedMeta> = vec![syn::parse_quote!(name = #name)];
+        if !doc.is_empty() {
+            added.push(syn::parse_quote!(doc = #doc));
+        }
+        if kind == "command" {
+            if prefix != "do_" {
+                attr_errors.extend(Error::new(
+                    span, "#[secop::command] must be placed on a do_ method").to_compile_error());
+                continue;
+            }
+            match parse_method_attr::<SecopCommand>(&attr, added) {
+                Ok(cmd) => commands.push((span, cmd)),
+                Err(err) => attr_errors.extend(err),
+            }
+            continue;
+        }
+        let has_read = method_names.contains(&format!("read_{}", name));
+        let has_write = method_names.contains(&format!("write_{}", name));
+        let (readonly, swonly) = match prefix {
+            "read_" | "write_" if has_read => (!has_write, false),
+            "write_" => {
+                attr_errors.extend(Error::new(
+                    span, format!("parameter {} has no read_{} method", name, name)).to_compile_error());
+                continue;
+            }
+            "update_" => (false, true),
+            _ => {
+                attr_errors.extend(Error::new(
+                    span, "#[secop::param] must be placed on a read_, write_ or update_ method")
+                                   .to_compile_error());
+                continue;
+            }
+        };
+        added.push(syn::parse_quote!(readonly = #readonly));
+        added.push(syn::parse_quote!(swonly = #swonly));
+        match parse_method_attr::<SecopParam>(&attr, added) {
+            Ok(param) => params.push((span, param)),
+            Err(err) => attr_errors.extend(err),
         }
-        _ => try_!(Err(Error::new(input.ast().ident.span(),
-                                  "derive(ModuleBase) is only possible for a \
-                                   struct with named fields")))
+    }
+    // Without a complete handler trait, the methods are kept as inherent
+    // methods, so that they don't cause further errors.
+    if !attr_errors.is_empty() {
+        item.trait_ = None;
     }
 
-    if !has_internals || !has_params {
-        try_!(Err(Error::new(input.ast().ident.span(),
-                             format!("struct {} must have \"internals: ModInternals\" \
-                                      and \"params: {}Params\" members", name, name))));
+    // The struct is not visible here, so the generated parameter struct and
+    // handler trait are private to the module that contains the impl block.
+    let generated = generate_module(ModuleSpec {
+        name,
+        generics: item.generics.clone(),
+        self_ty: (*item.self_ty).clone(),
+        vis: syn::Visibility::Inherited,
+        doc,
+        params,
+        commands,
+        properties,
+        stabilizer,
+        errors: attr_errors,
+    });
+    quote! {
+        #item
+        #generated
     }
+}
+
+/// Generate the module implementation: the `ModuleBase` impl, the parameter
+/// struct and the handler trait.
+fn generate_module(spec: ModuleSpec) -> TokenStream {
+    let ModuleSpec { name, generics, self_ty, vis, doc: class_doc, params, commands,
+                     properties, stabilizer, errors: mut error_tokens } = spec;
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let param_struct_name = format_ident!("{}Params", name);
+    let handlers_name = format_ident!("{}Handlers", name);
 
     // We need to check names for uniqueness, after lowercasing.
     // TODO: also check groups which are in the same namespace.
@@ -229,71 +636,190 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
     let mut param_members = vec![];
     let mut param_initializers = vec![];
     let mut statics = vec![];
-    let mut poll_busy_params = vec![];
-    let mut poll_other_params = vec![];
+    let mut polled_params = vec![];
     let mut activate_updates = vec![];
     let mut init_params_swonly = vec![];
     let mut init_params_write = vec![];
     let mut init_params_read = vec![];
+    let mut module_properties = vec![];
+    let mut writable_params = HashSet::new();
+    let mut param_infos = vec![];
+    let mut persistent_params = vec![];
+    let mut history_arms = vec![];
+    let mut history_enabled = vec![];
+    let mut static_history = false;
+    let mut command_infos = vec![];
+    let mut handler_methods = vec![];
+
+    // Errors are collected to report all of them at once.  Parameters and
+    // commands that cannot be generated at all are skipped.
+    let mut errors = Errors::default();
+    // Types generated for long inline tuples, named after the module and the
+    // parameter or command.
+    let mut tuples = TupleTypes::new(quote!(secop_core), &vis);
+    let class_name = name.to_string();
 
     for (span,
-         SecopParam { name, doc, datainfo, readonly, swonly, mandatory, polling,
-                      default, unit, group, visibility }) in params {
-        let polling = polling.unwrap_or(if swonly { 0 } else { 1 });
+         SecopParam { name, doc, datainfo, readonly, swonly, constant, mandatory, poll,
+                      busy_poll, polling, history, default, module_ref, persistent, qualifiers,
+                      unit, group, visibility }) in params {
+        let (poll, busy_poll) = match polling {
+            None => (poll, busy_poll),
+            Some(_) if poll.is_some() || busy_poll.is_some() => {
+                errors.push(Error::new(span, "polling is deprecated and cannot be combined \
+                                              with poll and busy_poll"));
+                (poll, busy_poll)
+            }
+            Some(Polling(1)) => (None, None),
+            Some(Polling(0)) => (Some(0.0), None),
+            Some(Polling(-1)) => (None, Some(0.0)),
+            Some(Polling(n)) => {
+                errors.push(Error::new(span, format!(
+                    "polling={} is no longer supported, use poll=\"<seconds>\" and \
+                     busy_poll=\"<seconds>\" instead", n)));
+                (poll, busy_poll)
+            }
+        };
+        if constant && !readonly {
+            errors.push(Error::new(span, "constant parameters must be readonly"));
+        }
+        let swonly = swonly || constant;
+        let polled = poll.map_or(!swonly, |v| v != 0.0);
 
         // Check necessary invariants.
         if !lc_names.insert(name.to_lowercase()) {
-            try_!(Err(Error::new(span, "param/cmd name is not unique")));
+            errors.push(Error::new(span, "param/cmd name is not unique"));
+            continue;
         }
         if !VISIBILITIES.iter().any(|&v| v == visibility) {
-            try_!(Err(Error::new(span, "visibility is not an allowed value")));
+            errors.push(Error::new(span, "visibility is not an allowed value"));
+        }
+        if poll.map_or(false, |v| v < 0.0) || busy_poll.map_or(false, |v| v < 0.0) {
+            errors.push(Error::new(span, "poll intervals cannot be negative"));
         }
         if swonly {
-            if polling != 0 {
-                try_!(Err(Error::new(span, "software-only parameters cannot be polled")));
+            if polled {
+                errors.push(Error::new(span, "software-only parameters cannot be polled"));
             }
             if default.is_none() && !mandatory {
-                try_!(Err(Error::new(span, "software-only parameters must have a default if not mandatory")));
+                errors.push(Error::new(span, "software-only parameters must have a default if not mandatory"));
+            }
+            if qualifiers {
+                errors.push(Error::new(span, "software-only parameters cannot have qualifiers"));
             }
         } else {
             if default.is_some() && readonly {
-                try_!(Err(Error::new(span, "readonly hardware parameters cannot have a default")));
+                errors.push(Error::new(span, "readonly hardware parameters cannot have a default"));
             }
             if mandatory && readonly {
-                try_!(Err(Error::new(span, "readonly hardware parameters cannot be mandatory")));
+                errors.push(Error::new(span, "readonly hardware parameters cannot be mandatory"));
             }
         }
 
-        let name_id = format_ident!("{}", name);
+        if persistent && readonly {
+            errors.push(Error::new(span, "readonly parameters cannot be persistent"));
+        }
 
         // Populate members of the parameter cache struct.
-        let (type_t, type_expr) = try_!(crate::parse_datainfo(span, &datainfo));
+        let tuple_name = format!("{}{}", class_name, camel_case(&name));
+        let (type_t, type_expr) = match crate::parse_datainfo(span, &datainfo, &tuple_name,
+                                                              &mut tuples) {
+            Ok(v) => v,
+            Err(e) => { errors.push(e); continue; }
+        };
+
+        if !readonly {
+            writable_params.insert(name.clone());
+        }
+        if persistent {
+            persistent_params.push(name.clone());
+        }
+
+        let name_id = format_ident!("{}", name);
+
+        // Members of plain enums can be given in the config.
+        let type_str = type_t.to_string();
+        let is_type = |t: &str| type_str == t || type_str.ends_with(&format!(":: {}", t));
+        let config_enum = is_type("Enum");
         param_members.push(quote! { #name_id: secop_core::module::ModParam<#type_t>, });
-        param_initializers.push(quote! { #name_id: secop_core::module::ModParam::new(#type_expr), });
+        param_initializers.push(quote! {
+            #name_id: secop_core::module::ModParam::new(#type_expr).with_history(#history),
+        });
         let par = quote!(self.params.#name_id);
+        // The history of numeric parameters can also be enabled in the config.
+        if ["Double", "Int", "Scaled"].iter().any(|t| is_type(t)) {
+            history_arms.push(quote! { #name => #par.history(), });
+            history_enabled.push(quote! { #par.history().is_some() });
+            static_history |= history > 0;
+        } else if history > 0 {
+            errors.push(Error::new(span, "only numeric parameters can have a history"));
+        }
 
         // Generate trampolines for read and write of the parameter.  These
-        // methods are expected to be present as inherent methods on the struct.
-        // If forgotten, the errors should be pretty clear.
+        // methods are declared in the handler trait, which the user implements
+        // for the struct.  If forgotten, the errors should be pretty clear.
         let read_method = format_ident!("read_{}", name);
         let write_method = format_ident!("write_{}", name);
         let update_method = format_ident!("update_{}", name);
 
+        // Declare the methods in the handler trait, which the user implements.
+        let repr_t = quote!(<#type_t as secop_core::types::TypeInfo>::Repr);
+        if !swonly {
+            let read_doc = format!("Read the current value of `{}` from the hardware.", name);
+            let read_t = if qualifiers {
+                quote!((#repr_t, secop_core::module::Qualifiers))
+            } else {
+                repr_t.clone()
+            };
+            handler_methods.push(quote! {
+                #[doc = #read_doc]
+                fn #read_method(&mut self) -> secop_core::errors::Result<#read_t>;
+            });
+            if !readonly {
+                let write_doc = format!("Write a new value of `{}` to the hardware.", name);
+                handler_methods.push(quote! {
+                    #[doc = #write_doc]
+                    fn #write_method(&mut self, value: #repr_t) -> secop_core::errors::Result<()>;
+                });
+            }
+        } else if !readonly {
+            let update_doc = format!("Called when the value of `{}` has changed.", name);
+            let body = if BUILTIN_UPDATES.contains(&&*name) ||
+                (stabilizer && STABILIZER_UPDATES.contains(&&*name)) {
+                quote! { secop_core::module::ModuleBase::#update_method(self, value) }
+            } else {
+                quote! { let _ = value; Ok(()) }
+            };
+            handler_methods.push(quote! {
+                #[doc = #update_doc]
+                fn #update_method(&mut self, value: #repr_t) -> secop_core::errors::Result<()> {
+                    #body
+                }
+            });
+        }
+
+        let read_call = if qualifiers {
+            quote! { <Self as #handlers_name>::#read_method(self)? }
+        } else {
+            quote! { (<Self as #handlers_name>::#read_method(self)?,
+                      secop_core::module::Qualifiers::default()) }
+        };
         par_read_arms.push(match swonly {
             false => quote! {
                 #name => (|| {
-                    let read_value = self.#read_method()?;
-                    let (value, time, send) = #par.update(read_value)?;
+                    let (read_value, qualifiers) = #read_call;
+                    let now = self.internals().clock().now();
+                    let (value, time, send) = #par.update_qualified(read_value, qualifiers, now)?;
                     if send {
-                        self.send_update(#name, value.clone(), time);
+                        self.send_update(#name, value.clone(), #par.qualifiers());
                     }
-                    Ok((value, time))
+                    Ok((value, time, #par.qualifiers()))
                 })()
             },
             true => quote! {
                 #name => (|| {
                     let value = #par.to_json()?;
-                    Ok((value, #par.time()))
+                    Ok((value, #par.time(), #par.qualifiers()))
                 })()
             },
         });
@@ -301,20 +827,22 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
         par_write_arms.push(match (swonly, readonly) {
             (false, false) => quote! {
                 #name => (|| {
-                    self.#write_method(#par.info.from_json(&value)?)?;
+                    let value = #par.info.from_json(&value)?;
+                    <Self as #handlers_name>::#write_method(self, value)?;
                     self.read(#name)
                 })()
             },
             (true, false) => quote! {
                 #name => (|| {
                     // TODO: simplify?
-                    let (value, time, send) =
-                        #par.update(#par.info.from_json(&value)?)?;
+                    let now = self.internals().clock().now();
+                    let (value, _, send) = #par.update(#par.info.from_json(&value)?, now)?;
                     if send {
-                        self.send_update(#name, value.clone(), time);
-                        self.#update_method(#par.clone())?;  // TODO why clone?
+                        self.send_update(#name, value.clone(), #par.qualifiers());
+                        let value = (*#par).clone();
+                        <Self as #handlers_name>::#update_method(self, value)?;
                     }
-                    Ok(json!([value, {"t": time}]))
+                    Ok(json!([value, #par.qualifiers()]))
                 })()
             },
             (_, true)  => quote! {
@@ -322,30 +850,53 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
             },
         });
 
-        // Generate entry for the polling loop.
-        if polling != 0 {
-            let polling_period = polling.abs() as usize;
-            let poll_it = quote! {
-                if n % #polling_period == 0 {
-                    // TODO: error handling (should send a error_update message)
-                    let _ = self.read(#name);
-                }
+        // Generate entry for the poll scheduler.
+        if polled {
+            let normal = match poll {
+                Some(v) => quote! { Some(#v) },
+                None => quote! { None },
             };
-            if polling > 0 {
-                poll_busy_params.push(poll_it);
-            } else {
-                poll_other_params.push(poll_it);
-            }
+            let busy = match busy_poll {
+                Some(v) => quote! { Some(#v) },
+                None => quote! { None },
+            };
+            polled_params.push(quote! {
+                (#name, secop_core::module::PollInterval { normal: #normal, busy: #busy })
+            });
         }
 
         // Generate entries for the "initial updates" phase of activation.
-        activate_updates.push(quote! {
-            // TODO: really ignore errors?
-            if let Ok(value) = #par.to_json() {
-                res.push(Msg::Update { module: self.name().to_string(),
-                                       param: #name.to_string(),
-                                       data: json!([value, {"t": #par.time()}]) });
-            }
+        // Constants are only transmitted in the description.
+        if !constant {
+            activate_updates.push(quote! {
+                // TODO: really ignore errors?
+                if let Ok(value) = #par.to_json() {
+                    res.push(Msg::Update { module: self.name().to_string(),
+                                           param: #name.to_string(),
+                                           data: json!([value, #par.qualifiers()]) });
+                }
+            });
+        }
+
+        // Static information, used to check the config before startup.
+        let default_str = match &default {
+            Some(def) => quote!(Some(#def)),
+            None => quote!(None),
+        };
+        param_infos.push(quote! {
+            secop_core::module::ParamInfo {
+                name: #name,
+                doc: #doc,
+                datainfo: serde_json::to_value(&#type_expr).unwrap(),
+                readonly: #readonly,
+                swonly: #swonly,
+                mandatory: #mandatory,
+                default: #default_str,
+                module_ref: #module_ref,
+                history: #history,
+                config_enum: #config_enum,
+                check: |v| (#type_expr).from_json(v).map(|_| ()),
+            },
         });
 
         // Generate parameter initialization code.
@@ -354,22 +905,41 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
         // code, config file, hardware) and multiple ways of using them
         // (depending on whether the parameter is writable at runtime).
         //
-        // TODO: check mandatory (where?)
+        // Presence of mandatory parameters is checked when loading the config.
         let def_expr = match default {
             None => None,
-            Some(def) => Some(try_!(syn::parse_str::<Expr>(&def),
-                                    "unparseable default value for param {}", name))
+            Some(def) => match syn::parse_str::<Expr>(&def) {
+                Ok(expr) => Some(expr),
+                Err(mut e) => {
+                    e.combine(Error::new(e.span(), format!(
+                        "unparseable default value for param {}", name)));
+                    errors.push(e);
+                    None
+                }
+            }
         };
         let def_option = def_expr.map_or(quote!(None::<fn() -> <#type_t as secop_core::types::TypeInfo>::Repr>),
                                          |expr| quote!(Some(|| #expr)));
         let upd_closure = if swonly && !readonly {
-            quote! { |slf, v| slf.#update_method(v) }
+            quote! { |slf, v| <Self as #handlers_name>::#update_method(slf, v) }
         } else {
             quote! { |_, _| Ok(()) }
         };
+        let enum_stanza = if config_enum {
+            quote! {
+                if let Some(members) = self.config().enums.get(#name).cloned() {
+                    #par.info = match secop_core::types::Enum::from_config(&members) {
+                        Ok(info) => info,
+                        Err(e) => return Err(e.amend(concat!("while initializing parameter ", #name))),
+                    };
+                }
+            }
+        } else { quote! {} };
         let init_stanza = quote! {
+            #enum_stanza
             if let Err(e) = self.init_parameter(#name, |slf| &mut slf.params.#name_id,
-                                                #upd_closure, #swonly, #readonly, #def_option) {
+                                                #upd_closure, #swonly, #readonly, #persistent,
+                                                #def_option) {
                 return Err(e.amend(concat!("while initializing parameter ", #name)));
             }
         };
@@ -386,6 +956,9 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
             let unit_entry = if !unit.is_empty() {
                 quote! { "unit": #unit, }
             } else { quote! {} };
+            let constant_entry = if constant {
+                quote! { "constant": #par.to_json().unwrap_or_default(), }
+            } else { quote! {} };
             descriptive.push(quote! {
                 #name: {
                     "description": #doc,
@@ -394,6 +967,7 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
                     "group": #group,
                     "visibility": #visibility,
                     #unit_entry
+                    #constant_entry
                 },
             });
         }
@@ -404,28 +978,54 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
     for (span,
          SecopCommand { name, doc, argtype, restype, group, visibility }) in commands {
         if !lc_names.insert(name.to_lowercase()) {
-            try_!(Err(Error::new(span, "param/cmd name is not unique")));
+            errors.push(Error::new(span, "param/cmd name is not unique"));
+            continue;
         }
         if !VISIBILITIES.iter().any(|&v| v == visibility) {
-            try_!(Err(Error::new(span, "visibility is not an allowed value")));
+            errors.push(Error::new(span, "visibility is not an allowed value"));
         }
 
         let argtype_static = format_ident!("CMD_ARG_{}", name);
-        let (argtype_t, argtype) = try_!(crate::parse_datainfo(span, &argtype));
         let restype_static = format_ident!("CMD_RES_{}", name);
-        let (restype_t, restype) = try_!(crate::parse_datainfo(span, &restype));
+        let tuple_name = format!("{}{}", class_name, camel_case(&name));
+        let argtype = crate::parse_datainfo(span, &argtype, &format!("{}Arg", tuple_name),
+                                            &mut tuples);
+        let restype = crate::parse_datainfo(span, &restype, &format!("{}Result", tuple_name),
+                                            &mut tuples);
+        let ((argtype_t, argtype), (restype_t, restype)) = match (argtype, restype) {
+            (Ok(arg), Ok(res)) => (arg, res),
+            (arg, res) => {
+                arg.err().into_iter().chain(res.err()).for_each(|e| errors.push(e));
+                continue;
+            }
+        };
         let do_method = format_ident!("do_{}", name);
+        let do_doc = format!("Execute the `{}` command.", name);
+        handler_methods.push(quote! {
+            #[doc = #do_doc]
+            fn #do_method(&mut self, arg: <#argtype_t as secop_core::types::TypeInfo>::Repr)
+                          -> secop_core::errors::Result<<#restype_t as secop_core::types::TypeInfo>::Repr>;
+        });
         statics.push(quote! {
             static ref #argtype_static: #argtype_t = #argtype;
             static ref #restype_static: #restype_t = #restype;
         });
         cmd_arms.push(quote! {
             #name => (|| {
-                let result_r = self.#do_method(#argtype_static.from_json(&arg)?)?;
+                let arg = #argtype_static.from_json(&arg)?;
+                let result_r = <Self as #handlers_name>::#do_method(self, arg)?;
                 let result = #restype_static.to_json(result_r)?;
-                Ok(json!([result, {"t": localtime()}]))
+                Ok(json!([result, {"t": self.internals().clock().now()}]))
             })()
         });
+        command_infos.push(quote! {
+            secop_core::module::CommandInfo {
+                name: #name,
+                doc: #doc,
+                argument: serde_json::to_value(&*#argtype_static).unwrap(),
+                result: serde_json::to_value(&*#restype_static).unwrap(),
+            },
+        });
         if visibility != "none" {
             descriptive.push(quote! {
                 #name: {
@@ -440,16 +1040,163 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
         }
     }
 
-    // So that we can interpolate it twice below.
-    let poll_busy_params = &poll_busy_params;
+    // Parameters with a history get a command to query it.  It is only
+    // described if a history is enabled, which the config can also do.
+    let mut history_descr = quote! {};
+    if !history_arms.is_empty() && !lc_names.insert("get_history".into()) {
+        errors.push(Error::new(Span::call_site(), "param/cmd name get_history is reserved \
+                                                   for modules with numeric parameters"));
+    } else if !history_arms.is_empty() {
+        let name = "get_history";
+        let doc = "get past values of a parameter, and their statistics";
+        cmd_arms.push(quote! {
+            #name => (|| {
+                use secop_core::history::{HistoryQueryType, HistoryDataType};
+                let query = HistoryQueryType.from_json(&arg)?;
+                let now = self.internals().clock().now();
+                let history = match &*query.param {
+                    #( #history_arms )*
+                    _ => None
+                }.ok_or_else(|| Error::bad_value(
+                    format!("parameter {} has no history", query.param)))?;
+                let result = HistoryDataType.to_json(history.query(query.window, now))?;
+                Ok(json!([result, {"t": now}]))
+            })()
+        });
+        if static_history {
+            command_infos.push(quote! {
+                secop_core::module::CommandInfo {
+                    name: #name,
+                    doc: #doc,
+                    argument: serde_json::to_value(&secop_core::history::HistoryQueryType).unwrap(),
+                    result: serde_json::to_value(&secop_core::history::HistoryDataType).unwrap(),
+                },
+            });
+        }
+        history_descr = quote! {
+            if #( #history_enabled )||* {
+                descr["accessibles"][#name] = json!({
+                    "description": #doc,
+                    "datainfo": {"type": "command",
+                                 "argument": serde_json::to_value(
+                                     &secop_core::history::HistoryQueryType).unwrap(),
+                                 "result": serde_json::to_value(
+                                     &secop_core::history::HistoryDataType).unwrap()},
+                    "group": "",
+                    "visibility": "user",
+                });
+            }
+        };
+    }
+
+    // Module properties declared in the code.  The interface classes are
+    // inferred from the accessibles if not given explicitly.
+    let mut has_interface_classes = false;
+    for (span, SecopProperty { name, value }) in properties {
+        if !MODULE_PROPERTIES.contains(&&*name) && !name.starts_with('_') {
+            errors.push(Error::new(span, "unknown module property (custom properties \
+                                          must start with an underscore)"));
+            continue;
+        }
+        has_interface_classes |= name == "interface_classes";
+        let value: TokenStream = match syn::parse_str(&value) {
+            Ok(value) => value,
+            Err(e) => {
+                errors.push(Error::new(span, format!("invalid property value: {}", e)));
+                continue;
+            }
+        };
+        module_properties.push(quote! { descr[#name] = json!(#value); });
+    }
+    if !has_interface_classes {
+        let interface_class = if !lc_names.contains("value") || !lc_names.contains("status") {
+            None
+        } else if !writable_params.contains("target") {
+            Some("Readable")
+        } else if !lc_names.contains("stop") {
+            Some("Writable")
+        } else {
+            Some("Drivable")
+        };
+        module_properties.insert(0, quote! {
+            descr["interface_classes"] = json!([#interface_class]);
+        });
+    }
+    let implementation = name.to_string();
 
+    // For modules using the stabilizer, follow the approach of the value
+    // to the target.  Writes during initialization don't count.  When the
+    // target is reached or times out, the new status is sent right away.
+    let has_target = lc_names.contains("value") && writable_params.contains("target");
+    if stabilizer && !has_target {
+        errors.push(Error::new(Span::call_site(), "the stabilizer requires a value and a \
+                                                   writable target parameter"));
+    }
+    let (value_hook, target_hook, init_hook) =
+        if stabilizer && has_target {
+            (quote! {
+                if param == "value" {
+                    if let Some(value) = value.as_f64() {
+                        let before = self.internals().stabilizer().status();
+                        self.internals_mut().stabilizer_mut().update(value, time);
+                        if self.internals().stabilizer().status() != before {
+                            let _ = self.read("status");
+                        }
+                    }
+                }
+            }, quote! {
+                if param == "target" {
+                    if let Some(target) = data[0].as_f64() {
+                        let now = self.internals().clock().now();
+                        self.internals_mut().stabilizer_mut().start(target, now);
+                        let _ = self.read("status");
+                    }
+                }
+            }, quote! {
+                self.internals_mut().stabilizer_mut().stop();
+            })
+        } else {
+            (quote!(), quote!(), quote!())
+        };
     // Generate the final code.  Most is contained in the impl of ModuleBase,
     // some other bits are done below.
-    let generated_impl = input.gen_impl(quote! {
+    if let Err(e) = errors.finish() {
+        error_tokens.extend(e.to_compile_error());
+    }
+    let generated_impl = if !error_tokens.is_empty() {
+        // Only a stub, so that the errors are not followed by others about
+        // ModuleBase not being implemented.
+        quote! {
+            impl #impl_generics secop_core::module::ModuleBase for #self_ty #where_clause {
+                fn internals(&self) -> &secop_core::module::ModInternals { unreachable!() }
+                fn internals_mut(&mut self) -> &mut secop_core::module::ModInternals {
+                    unreachable!()
+                }
+                fn class_info() -> secop_core::module::ClassInfo { unreachable!() }
+                fn describe(&self) -> serde_json::Value { unreachable!() }
+                fn read(&mut self, _: &str) -> secop_core::errors::Result<serde_json::Value> {
+                    unreachable!()
+                }
+                fn change(&mut self, _: &str, _: serde_json::Value)
+                          -> secop_core::errors::Result<serde_json::Value> {
+                    unreachable!()
+                }
+                fn command(&mut self, _: &str, _: serde_json::Value)
+                           -> secop_core::errors::Result<serde_json::Value> {
+                    unreachable!()
+                }
+                fn activate_updates(&mut self) -> Vec<secop_core::proto::Msg> { unreachable!() }
+                fn init_params(&mut self) -> secop_core::errors::Result<()> { unreachable!() }
+                fn polled_params() -> Vec<(&'static str, secop_core::module::PollInterval)> {
+                    unreachable!()
+                }
+                fn is_busy(&self) -> bool { unreachable!() }
+            }
+        }
+    } else { quote! { const _: () = {
         // Try to `use` all necessary APIs here.
         use serde_json::{Value, json};
         use lazy_static::lazy_static;
-        use mlzutil::time::localtime;
         use secop_core::errors::{Error, ErrorKind, Result};
         use secop_core::proto::Msg;
         use secop_core::module::ModuleBase;
@@ -459,21 +1206,36 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
             #( #statics )*
         }
 
-        gen impl ModuleBase for @Self {
+        impl #impl_generics ModuleBase for #self_ty #where_clause {
             fn internals(&self) -> &ModInternals { &self.internals }
             fn internals_mut(&mut self) -> &mut ModInternals { &mut self.internals }
 
+            fn class_info() -> secop_core::module::ClassInfo {
+                secop_core::module::ClassInfo {
+                    name: #implementation,
+                    doc: #class_doc,
+                    params: vec![#( #param_infos )*],
+                    commands: vec![#( #command_infos )*],
+                }
+            }
+
             fn describe(&self) -> Value {
-                json!({
+                let mut descr = json!({
                     "description": self.config().description,
-                    "interface_classes": ["Drivable"], // TODO
+                    "implementation": concat!(module_path!(), "::", #implementation),
                     "features": [],
                     "visibility": self.config().visibility,
                     "group": self.config().group,
                     "accessibles": {
                         #( #descriptive )*
                     }
-                })
+                });
+                #history_descr
+                #( #module_properties )*
+                for (key, value) in &self.config().properties {
+                    descr[key] = value.clone();
+                }
+                descr
             }
 
             fn read(&mut self, param: &str) -> Result<Value> {
@@ -483,7 +1245,10 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
                     _ => Err(Error::no_param())
                 };
                 match result {
-                    Ok((value, time)) => Ok(json!([value, {"t": time}])),
+                    Ok((value, time, qualifiers)) => {
+                        #value_hook
+                        Ok(json!([value, qualifiers]))
+                    }
                     Err(e) => {
                         error!("while reading parameter {}: {}", param, e);
                         Err(e)
@@ -493,12 +1258,18 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
 
             fn change(&mut self, param: &str, value: Value) -> Result<Value> {
                 debug!("changing parameter {} to {}", param, value);
-                let result = match param {
+                let result: Result<Value> = match param {
                     #( #par_write_arms, )*
                     _ => Err(Error::no_param())
                 };
-                if let Err(ref e) = result {
-                    error!("while changing parameter {} to {}: {}", param, value, e);
+                match result {
+                    Ok(ref data) => {
+                        if [#( #persistent_params ),*].contains(&param) {
+                            self.internals().persist(param, data[0].clone());
+                        }
+                        #target_hook
+                    }
+                    Err(ref e) => error!("while changing parameter {} to {}: {}", param, value, e),
                 }
                 result
             }
@@ -526,44 +1297,48 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
                 // read for the other parameters makes use of the written ones already.
                 #( #init_params_swonly )*
                 #( #init_params_write )*
+                #init_hook
                 #( #init_params_read )*
                 Ok(())
             }
 
-            fn poll_normal(&mut self, n: usize) {
-                // The parameters with special busy-poll handling are not polled here,
-                // to avoid polling them twice at the almost same time.
-                if self.params.status.0 != StatusConst::Busy {
-                    #( #poll_busy_params )*
-                }
-                #( #poll_other_params )*
+            fn polled_params() -> Vec<(&'static str, secop_core::module::PollInterval)> {
+                vec![ #( #polled_params ),* ]
             }
 
-            fn poll_busy(&mut self, n: usize) {
-                if self.params.status.0 == StatusConst::Busy {
-                    #( #poll_busy_params )*
-                }
+            fn is_busy(&self) -> bool {
+                self.params.status.0.is_busy()
             }
         }
-    });
 
-    // Implement Drop to be able to call the teardown function in every case,
-    // especially on panic.  (There is no inherent advantage to the user directly
-    // implementing Drop, but this puts setup and teardown closer together
-    // in the Module trait.)
-    let drop_impl = input.gen_impl(quote! {
-        gen impl Drop for @Self {
+        // Implement Drop to be able to call the teardown function in every case,
+        // especially on panic.  (There is no inherent advantage to the user directly
+        // implementing Drop, but this puts setup and teardown closer together
+        // in the Module trait.)
+        impl #impl_generics Drop for #self_ty #where_clause {
             fn drop(&mut self) {
                 self.teardown();
             }
         }
-    });
+    }; } };
 
+    let handlers_doc = format!("Handlers for reading and writing the parameters, and executing \
+                                the commands of `{}`.", name);
+    let tuple_items = tuples.into_items();
     let generated = quote! {
+        #error_tokens
+
+        #( #tuple_items )*
+
         #vis struct #param_struct_name {
             #( #param_members )*
         }
 
+        #[doc = #handlers_doc]
+        #vis trait #handlers_name: secop_core::module::ModuleBase {
+            #( #handler_methods )*
+        }
+
         impl Default for #param_struct_name {
             fn default() -> Self {
                 Self {
@@ -573,7 +1348,6 @@ pub fn derive_module(input: synstructure::Structure) -> TokenStream {
         }
 
         #generated_impl
-        #drop_impl
     };
     // println!("{}", generated);
     generated
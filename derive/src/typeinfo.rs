@@ -84,29 +84,122 @@
 //! The newly created `PIDType` describes a SECoP struct with members
 //! "p", "i" and "d", and the `PID` struct itself is used to pass values
 //! of this type to the internal methods.
+//!
+//! ## Tuple struct example
+//!
+//! Tuple structs result in SECoP tuples, again with a datainfo for each
+//! member:
+//!
+//! ```ignore
+//! #[derive(TypeInfo, Clone, PartialEq)]
+//! struct CalPoint(#[datainfo="Double()"] f64, #[datainfo="Str()"] String);
+//! ```
+//!
+//! A newtype, i.e. a tuple struct with only one member, has the datainfo of
+//! its member, so it can be used to add type safety on the Rust side.
+//!
+//! Inline tuple datainfos with more than 12 members get a generated tuple
+//! struct as their value, named after the struct and member, e.g. `PIDCoeffs`
+//! for member `coeffs` of `PID` (or `CalPoint1` for member 1 of `CalPoint`).
+//!
+//! ## Serde example
+//!
+//! Types that are converted through serde, using `Serde::<T>()` as the
+//! datainfo, need a `SerdeSchema` impl describing their serialized form.  For
+//! structs, it can be derived together with `Serialize` and `Deserialize`,
+//! using the same datainfo attributes:
+//!
+//! ```ignore
+//! #[derive(TypeInfo, Serialize, Deserialize, Clone, PartialEq)]
+//! #[serde_schema]
+//! struct GasFlow {
+//!     #[datainfo="GasType"]
+//!     gas: Gas,
+//!     #[datainfo="Double(unit=\"ml/min\")"]
+//!     flow: f64,
+//!     #[datainfo="Str()"]
+//!     comment: Option<String>,
+//! }
+//! ```
+//!
+//! In this case, no metatype is generated.  Serde's `rename` of fields is
+//! taken into account, while attributes that change the serialized shape
+//! otherwise (like `flatten` or `rename_all`) are rejected.
 
 
 use quote::{quote, format_ident};
 use darling::FromMeta;
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::TokenStream;
 use syn::spanned::Spanned;
 
+use crate::{Errors, TupleTypes, camel_case};
+
 
 pub fn derive_typeinfo(input: synstructure::Structure) -> TokenStream {
+    if let Some(attr) = input.ast().attrs.iter().find(|a| a.path.is_ident("serde_schema")) {
+        return derive_serde_schema(&input, attr);
+    }
     match input.ast().data {
         syn::Data::Struct(..) => derive_typeinfo_struct(input),
         syn::Data::Enum(..) => derive_typeinfo_enum(input),
-        _ => panic!("impossible to derive a TypeInfo for unions")
+        syn::Data::Union(..) => unreachable!("unions are rejected before"),
     }
 }
 
-macro_rules! try_ {
-    ($expr:expr) => (
-        match $expr {
-            Ok(v) => v,
-            Err(e) => return TokenStream::from(e.to_compile_error())
-        }
-    );
+/// Generate the metatype with a stub implementation, next to the errors, so
+/// that uses of the metatype don't cause further errors.
+fn stub_typeinfo(input: &synstructure::Structure, errors: syn::Error) -> TokenStream {
+    let name = &input.ast().ident;
+    let vis = &input.ast().vis;
+    let const_name = format_ident!("_DERIVE_TypeInfo_{}", name);
+    let struct_name = format_ident!("{}Type", name);
+    let errors = errors.to_compile_error();
+    quote! {
+        #errors
+
+        #[derive(Clone, Copy)]
+        #vis struct #struct_name;
+
+        #[allow(non_upper_case_globals)]
+        const #const_name: () = {
+            use serde::ser::{Serialize, Serializer};
+            use serde_json::Value;
+            use crate::secop_core::errors::Error;
+            use crate::secop_core::types::TypeInfo;
+
+            impl Serialize for #struct_name {
+                fn serialize<S>(&self, _: S) -> std::result::Result<S::Ok, S::Error> where S: Serializer
+                {
+                    unreachable!()
+                }
+            }
+
+            impl TypeInfo for #struct_name {
+                type Repr = #name;
+
+                fn to_json(&self, _: Self::Repr) -> std::result::Result<Value, Error> {
+                    unreachable!()
+                }
+
+                fn from_json(&self, _: &Value) -> std::result::Result<Self::Repr, Error> {
+                    unreachable!()
+                }
+            }
+        };
+    }
+}
+
+/// Find the `datainfo` attribute on a struct field, which must be present, and
+/// translate it into (datainfo type, datainfo value).  Long tuples get types
+/// named `tuple_name`.
+fn field_datainfo(field: &syn::Field, name: &str, tuple_name: &str, tuples: &mut TupleTypes)
+                  -> Result<(TokenStream, TokenStream), syn::Error> {
+    let attr = field.attrs.iter().find(|attr| attr.path.is_ident("datainfo")).ok_or_else(
+        || syn::Error::new(field.span(), format!("member {} has no datainfo attribute, \
+                                                  add #[datainfo=\"...\"]", name)))?;
+    let dtype = attr.parse_meta().ok().and_then(|meta| String::from_meta(&meta).ok()).ok_or_else(
+        || syn::Error::new(attr.span(), "invalid datainfo attribute, expected #[datainfo=\"...\"]"))?;
+    crate::parse_datainfo(attr.span(), &dtype, tuple_name, tuples)
 }
 
 pub fn derive_typeinfo_struct(input: synstructure::Structure) -> TokenStream {
@@ -125,31 +218,29 @@ pub fn derive_typeinfo_struct(input: synstructure::Structure) -> TokenStream {
     let mut descr_members = Vec::new();
     let mut descr_optional = Vec::new();
 
+    // Tuple structs are SECoP tuples, or the member's type for newtypes.
+    if matches!(input.ast().data, syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Unnamed(_), .. }))
+    {
+        return derive_typeinfo_tuple_struct(input);
+    }
+
     // Go through each field, and construct the SECoP metatype for it.
+    let mut errors = Errors::default();
+    let mut tuples = TupleTypes::new(quote!(crate::secop_core), vis);
     for binding in input.variants()[0].bindings() {
-        // We could support tuple structs to work the same as tuples, but it
-        // seems unnecessary at the moment.
-        let ident = binding.ast().ident.as_ref().unwrap_or_else(
-            || panic!("TypeInfo cannot be derived for tuple structs"));
+        let ident = binding.ast().ident.as_ref().unwrap();
         let ident_str = ident.to_string();
         // We need the metatype instance globally available somewhere.  Since
         // it cannot (currently) be constructed in a `const` context, it needs
         // to be a lazy static.
         let dtype_static = format_ident!("STRUCT_FIELD_{}", ident_str);
-        // Find the `datainfo` attribute on the field.  It must be present.
-        let mut dtype = None;
-        let mut span = Span::call_site();
-        for attr in &binding.ast().attrs {
-            if attr.path.segments[0].ident == "datainfo" {
-                dtype = attr.parse_meta().ok();
-                span = attr.span();
-            }
-        }
-        let dtype = dtype.unwrap_or_else(
-            || panic!("member {} has no valid datainfo attribute", ident_str));
-        let dtype = String::from_meta(&dtype).unwrap_or_else(
-            |e| panic!("member {} has no valid datainfo attribute: {}", ident_str, e));
-        let (dtype_t, dtype) = try_!(crate::parse_datainfo(span, &dtype));
+        let tuple_name = format!("{}{}", name, camel_case(&ident_str));
+        let (dtype_t, dtype) = match field_datainfo(binding.ast(), &ident_str, &tuple_name,
+                                                    &mut tuples) {
+            Ok(v) => v,
+            Err(e) => { errors.push(e); continue; }
+        };
         // Check if the Rust type is an Option.
         let mut is_option_type = false;
         if let syn::Type::Path(ref ptype) = binding.ast().ty {
@@ -196,10 +287,18 @@ pub fn derive_typeinfo_struct(input: synstructure::Structure) -> TokenStream {
             });
         }
         descr_members.push(quote! { (#ident_str, serde_json::to_value(&*#dtype_static).unwrap()), });
-        descr_optional.push(quote! { #ident_str, });
+        if is_option_type {
+            descr_optional.push(quote! { #ident_str, });
+        }
+    }
+    if let Err(e) = errors.finish() {
+        return stub_typeinfo(&input, e);
     }
 
+    let tuple_items = tuples.into_items();
     let generated = quote! {
+        #( #tuple_items )*
+
         #[derive(Clone, Copy)]
         #vis struct #struct_name;
 
@@ -224,8 +323,8 @@ pub fn derive_typeinfo_struct(input: synstructure::Structure) -> TokenStream {
                     let mut map = serializer.serialize_map(None)?;
                     map.serialize_entry("type", "struct")?;
                     map.serialize_entry("members", &members)?;
-                    let optional = [#( #descr_optional )*];
-                    if optional.len() < members.len() {
+                    let optional: &[&str] = &[#( #descr_optional )*];
+                    if !optional.is_empty() {
                         map.serialize_entry("optional", &optional)?;
                     }
                     map.end()
@@ -255,6 +354,224 @@ pub fn derive_typeinfo_struct(input: synstructure::Structure) -> TokenStream {
     generated
 }
 
+pub fn derive_typeinfo_tuple_struct(input: synstructure::Structure) -> TokenStream {
+    let name = &input.ast().ident;
+    let vis = &input.ast().vis;
+    let const_name = format_ident!("_DERIVE_TypeInfo_{}", name);
+    let struct_name = format_ident!("{}Type", name);
+
+    let mut statics = Vec::new();
+    let mut members = Vec::new();
+    let mut errors = Errors::default();
+    let mut tuples = TupleTypes::new(quote!(crate::secop_core), vis);
+    for (i, binding) in input.variants()[0].bindings().iter().enumerate() {
+        let dtype_static = format_ident!("TUPLE_FIELD_{}", i);
+        let (dtype_t, dtype) = match field_datainfo(binding.ast(), &i.to_string(),
+                                                    &format!("{}{}", name, i), &mut tuples) {
+            Ok(v) => v,
+            Err(e) => { errors.push(e); continue; }
+        };
+        statics.push(quote! {
+            static ref #dtype_static: #dtype_t = #dtype;
+        });
+        members.push((syn::Index::from(i), dtype_static));
+    }
+    if let Err(e) = errors.finish() {
+        return stub_typeinfo(&input, e);
+    }
+
+    // A newtype is transparent, i.e. it has the datainfo of its member.
+    let (serialize, to_json, from_json) = if let [(_, dtype_static)] = &members[..] {
+        (quote! {
+            #dtype_static.serialize(serializer)
+        }, quote! {
+            #dtype_static.to_json(val.0)
+        }, quote! {
+            Ok(#name(#dtype_static.from_json(val)?))
+        })
+    } else {
+        let len = members.len();
+        let indices = members.iter().map(|m| &m.0).collect::<Vec<_>>();
+        let statics = members.iter().map(|m| &m.1).collect::<Vec<_>>();
+        let items = (0..len).map(|i| format!("in item {}", i)).collect::<Vec<_>>();
+        let error = format!("expected array with {} elements", len);
+        (quote! {
+            use serde::ser::SerializeMap;
+            let members = [#( serde_json::to_value(&*#statics).unwrap() ),*];
+            let mut map = serializer.serialize_map(Some(2))?;
+            map.serialize_entry("type", "tuple")?;
+            map.serialize_entry("members", &members)?;
+            map.end()
+        }, quote! {
+            Ok(serde_json::json!([ #(
+                #statics.to_json(val.#indices).map_err(|e| e.amend(#items))?
+            ),* ]))
+        }, quote! {
+            match val.as_array() {
+                Some(arr) if arr.len() == #len => Ok(#name( #(
+                    #statics.from_json(&arr[#indices]).map_err(|e| e.amend(#items))?
+                ),* )),
+                _ => Err(Error::bad_value(#error))
+            }
+        })
+    };
+
+    let tuple_items = tuples.into_items();
+    let generated = quote! {
+        #( #tuple_items )*
+
+        #[derive(Clone, Copy)]
+        #vis struct #struct_name;
+
+        #[allow(non_upper_case_globals)]
+        const #const_name: () = {
+            use serde::ser::{Serialize, Serializer};
+            use serde_json::Value;
+            use lazy_static::lazy_static;
+            use crate::secop_core::errors::Error;
+            use crate::secop_core::types::TypeInfo;
+
+            lazy_static! {
+                #( #statics )*
+            }
+
+            impl Serialize for #struct_name {
+                fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> where S: Serializer
+                {
+                    #serialize
+                }
+            }
+
+            impl TypeInfo for #struct_name {
+                type Repr = #name;
+
+                fn to_json(&self, val: Self::Repr) -> std::result::Result<Value, Error> {
+                    #to_json
+                }
+
+                fn from_json(&self, val: &Value) -> std::result::Result<Self::Repr, Error> {
+                    #from_json
+                }
+            }
+        };
+    };
+    generated
+}
+
+/// The name under which serde (de)serializes a struct field.  Attributes that
+/// change the serialized shape in ways the schema cannot follow are rejected.
+fn serde_field_name(field: &syn::Field, ident: &str) -> Result<String, syn::Error> {
+    let mut name = ident.to_owned();
+    for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+        let list = match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list,
+            _ => continue,
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path, lit: syn::Lit::Str(s), .. })) if path.is_ident("rename") => {
+                    name = s.value();
+                }
+                syn::NestedMeta::Meta(meta) if UNSUPPORTED_SERDE_FIELD.iter().any(
+                    |n| meta.path().is_ident(n)) =>
+                {
+                    return Err(syn::Error::new(meta.span(), format!(
+                        "serde attribute {} is not supported with serde_schema",
+                        meta.path().get_ident().unwrap())));
+                }
+                _ => ()
+            }
+        }
+    }
+    Ok(name)
+}
+
+/// Serde field attributes that make the serialized shape differ from the fields.
+const UNSUPPORTED_SERDE_FIELD: &[&str] = &["rename", "flatten", "skip", "skip_serializing",
+                                           "skip_deserializing"];
+
+/// Serde container attributes that make the serialized shape differ from the fields.
+const UNSUPPORTED_SERDE_CONTAINER: &[&str] = &["rename_all", "transparent", "tag", "untagged",
+                                               "content", "into", "from", "try_from"];
+
+/// Generate the `SerdeSchema` impl for a struct with serde support, from the
+/// datainfo attributes of its fields.
+fn derive_serde_schema(input: &synstructure::Structure, flag: &syn::Attribute) -> TokenStream {
+    let name = &input.ast().ident;
+    let vis = &input.ast().vis;
+    let const_name = format_ident!("_DERIVE_TypeInfo_{}", name);
+
+    let mut errors = Errors::default();
+    let mut tuples = TupleTypes::new(quote!(crate::secop_core), vis);
+    let mut members = Vec::new();
+    if !matches!(flag.parse_meta(), Ok(syn::Meta::Path(_))) {
+        errors.push(syn::Error::new(flag.span(), "expected #[serde_schema] without arguments"));
+    }
+    for attr in input.ast().attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+        if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let syn::NestedMeta::Meta(meta) = nested {
+                    if UNSUPPORTED_SERDE_CONTAINER.iter().any(|n| meta.path().is_ident(n)) {
+                        errors.push(syn::Error::new(meta.span(), format!(
+                            "serde attribute {} is not supported with serde_schema",
+                            meta.path().get_ident().unwrap())));
+                    }
+                }
+            }
+        }
+    }
+    if matches!(input.ast().data, syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(_), .. }))
+    {
+        for binding in input.variants()[0].bindings() {
+            let ident_str = binding.ast().ident.as_ref().unwrap().to_string();
+            let tuple_name = format!("{}{}", name, camel_case(&ident_str));
+            let field_name = match serde_field_name(binding.ast(), &ident_str) {
+                Ok(v) => v,
+                Err(e) => { errors.push(e); continue; }
+            };
+            let (_, dtype) = match field_datainfo(binding.ast(), &ident_str, &tuple_name,
+                                                  &mut tuples) {
+                Ok(v) => v,
+                Err(e) => { errors.push(e); continue; }
+            };
+            let is_option_type = matches!(&binding.ast().ty, syn::Type::Path(ptype)
+                                          if ptype.path.segments[0].ident == "Option");
+            if is_option_type {
+                members.push(quote! { .optional(#field_name, #dtype) });
+            } else {
+                members.push(quote! { .member(#field_name, #dtype) });
+            }
+        }
+    } else {
+        errors.push(syn::Error::new(flag.span(), "serde_schema can only be used on structs \
+                                                  with named fields"));
+    }
+
+    let (errors, datainfo) = match errors.finish() {
+        Ok(()) => (None, quote! { AnyType::new(Struct::new() #( #members )*) }),
+        Err(e) => (Some(e.to_compile_error()), quote! { unreachable!() }),
+    };
+    let tuple_items = tuples.into_items();
+    quote! {
+        #errors
+
+        #( #tuple_items )*
+
+        #[allow(non_upper_case_globals)]
+        const #const_name: () = {
+            use crate::secop_core::types::{AnyType, SerdeSchema, Struct};
+
+            impl SerdeSchema for #name {
+                fn datainfo() -> AnyType {
+                    #datainfo
+                }
+            }
+        };
+    }
+}
+
 pub fn derive_typeinfo_enum(input: synstructure::Structure) -> TokenStream {
     let name = &input.ast().ident;
     let vis = &input.ast().vis;
@@ -264,19 +581,27 @@ pub fn derive_typeinfo_enum(input: synstructure::Structure) -> TokenStream {
     let mut descr_members = Vec::new();
     let mut str_arms = Vec::new();
     let mut int_arms = Vec::new();
+    let mut name_arms = Vec::new();
 
     let mut discr = -1i64;
+    let mut errors = Errors::default();
     for variant in input.variants() {
         let ident = &variant.ast().ident;
         let ident_str = ident.to_string();
         if variant.ast().fields != &syn::Fields::Unit {
-            panic!("enum member {} cannot have data associated with it", ident);
+            errors.push(syn::Error::new(variant.ast().fields.span(), format!(
+                "enum member {} cannot have data associated with it", ident)));
+            continue;
         }
         if let Some((_, dis)) = variant.ast().discriminant {
-            if let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) = dis {
-                discr = i.base10_parse().unwrap();
-            } else {
-                panic!("explicit enum discriminants can only be integer literals");
+            match crate::parse_int(dis) {
+                Some(Ok(v)) => discr = v,
+                Some(Err(e)) => { errors.push(e); continue; }
+                None => {
+                    errors.push(syn::Error::new(dis.span(), "explicit enum discriminants \
+                                                             can only be integer literals"));
+                    continue;
+                }
             }
         } else {
             discr += 1;
@@ -284,6 +609,10 @@ pub fn derive_typeinfo_enum(input: synstructure::Structure) -> TokenStream {
         descr_members.push(quote! { (#ident_str, #discr), });
         str_arms.push(quote! { #ident_str => Ok(#name::#ident), });
         int_arms.push(quote! { #discr => Ok(#name::#ident), });
+        name_arms.push(quote! { #name::#ident => #ident_str, });
+    }
+    if let Err(e) = errors.finish() {
+        return stub_typeinfo(&input, e);
     }
 
     let generated = quote! {
@@ -334,6 +663,10 @@ pub fn derive_typeinfo_enum(input: synstructure::Structure) -> TokenStream {
                         Err(Error::bad_value("expected string or integer"))
                     }
                 }
+
+                fn to_serde(&self, val: &Value) -> std::result::Result<Value, Error> {
+                    Ok(json!(match self.from_json(val)? { #( #name_arms )* }))
+                }
             }
         };
     };
@@ -0,0 +1,43 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Example plugin, providing the simulated cryostat under another class name.
+//!
+//! Build with `cargo build -p secop-example-plugin` and add the library to
+//! the config:
+//!
+//! ```toml
+//! plugins = ["target/debug/libsecop_example_plugin.so"]
+//!
+//! [modules.cryo]
+//! class = "PluginCryo"
+//! ...
+//! ```
+
+use secop_core::registry::Registry;
+use secop_modules::simcryo::SimCryo;
+
+fn register(registry: &mut Registry) {
+    registry.register_as::<SimCryo, _>("PluginCryo");
+}
+
+secop_core::export_plugin!(register);
@@ -0,0 +1,61 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for loading the example plugin.
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::{Path, PathBuf};
+
+use secop_core::plugin::load_plugin;
+use secop_core::registry::Registry;
+
+fn plugin_path() -> PathBuf {
+    // the test executable is in target/<profile>/deps, the plugin next to it
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(format!("{}secop_example_plugin{}", DLL_PREFIX, DLL_SUFFIX));
+    path
+}
+
+#[test]
+fn load_example_plugin() {
+    let mut registry = Registry::new();
+    load_plugin(&mut registry, &plugin_path()).unwrap();
+    assert_eq!(registry.class_names().collect::<Vec<_>>(), ["PluginCryo"]);
+    let info = registry.class_info("PluginCryo").unwrap();
+    assert_eq!(info.name, "SimCryo");
+    assert!(info.param("pid").is_some());
+}
+
+#[test]
+fn load_missing_plugin() {
+    let mut registry = Registry::new();
+    assert!(load_plugin(&mut registry, &plugin_path().with_file_name("nonexisting.so")).is_err());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn load_invalid_plugin() {
+    let mut registry = Registry::new();
+    let err = load_plugin(&mut registry, Path::new("libc.so.6")).unwrap_err();
+    assert_eq!(err, "library is not a SECoP plugin");
+}
@@ -20,53 +20,38 @@
 //
 // -----------------------------------------------------------------------------
 //
-//! Module dispatcher.
+//! Module classes provided with the server.
 
 extern crate secop_core;
 
-mod simcryo;
+pub mod simcryo;
+pub mod proxy;
 mod serial;
 mod tcp;
 mod toellner;
 
 pub(crate) mod support;
 
-use std::panic::catch_unwind;
 use std::error::Error as StdError;
-use std::time::Duration;
-use std::thread::{Builder, sleep};
-use log::*;
-
-use secop_core::module::{Module, ModInternals};
-
-
-/// Inner (generic) implementation of `run_module`.
-fn inner_run<T: Module>(internals: ModInternals) {
-    let name = internals.name().to_owned();
-    Builder::new().name(name.clone()).spawn(move || loop {
-        if catch_unwind(|| {
-            T::create(internals.clone()).expect("init failed").run()
-        }).is_err() {
-            error!("module {} panicked, waiting...", name);
-            // remove all pending requests
-            internals.req_receiver().try_iter().count();
-            // wait for another request to arrive
-            while internals.req_receiver().is_empty() {
-                sleep(Duration::from_millis(100));
-            }
-            info!("now restarting module {}", name);
-        }
-    }).expect("could not start thread");
+use secop_core::module::ModInternals;
+use secop_core::registry::Registry;
+
+
+/// Register all module classes of this crate.
+pub fn register(registry: &mut Registry) {
+    secop_core::register_classes!(registry;
+        simcryo::SimCryo,
+        serial::SerialComm,
+        tcp::TcpComm,
+        toellner::ToellnerPS,
+        proxy::Proxy,
+    );
 }
 
-
-/// Start the module's own thread.
+/// Start the module's own thread, for a class of this crate.
+#[deprecated(note = "use `register` and `Registry::run_module` instead")]
 pub fn run_module(internals: ModInternals) -> Result<(), Box<dyn StdError>> {
-    Ok(match &*internals.class() {
-        "SimCryo" => inner_run::<simcryo::SimCryo>(internals),
-        "SerialComm" => inner_run::<serial::SerialComm>(internals),
-        "TcpComm" => inner_run::<tcp::TcpComm>(internals),
-        "ToellnerPS" => inner_run::<toellner::ToellnerPS>(internals),
-        _ => return Err(format!("no such module class: {}", internals.class()).into())
-    })
+    let mut registry = Registry::new();
+    register(&mut registry);
+    registry.run_module(internals)
 }
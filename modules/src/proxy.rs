@@ -0,0 +1,354 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Module that makes a module of a remote SEC node available in this node.
+//!
+//! The description of the remote module is imported on connection, all
+//! requests are forwarded, and updates from the remote node are relayed to
+//! our clients.  Since the accessibles are only known at runtime, this module
+//! implements `ModuleBase` by hand instead of deriving it.
+//!
+//! Configuration parameters:
+//!
+//! * `uri`: address of the remote node as `host:port` (mandatory)
+//! * `module`: name of the module on the remote node (default: local name)
+//! * `reconnect`: seconds between reconnection attempts, 0 to retry on
+//!   every poll (default: 10)
+//! * `timeout`: seconds to wait for replies from the remote node (default: 2)
+//! * `offline_status`: status code reported while the remote module is not
+//!   available, e.g. `"Error"` or `"Disabled"` (default: `"Error"`)
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use log::*;
+use parking_lot::Mutex;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::client::{RemoteClient, DEFAULT_TIMEOUT};
+use secop_core::config::Visibility;
+use secop_core::proto::Msg;
+use secop_core::server::{ModRepSender, RepReceiver};
+use secop_core::module::{ClassInfo, ParamInfo, PollInterval, status_only_description};
+use secop_core::types::{Double, Str, StatusConstType};
+
+const DEFAULT_PORT: u16 = 10767;
+const RECONNECT_TYPE: Double = Double::new().min(0.0);
+const TIMEOUT_TYPE: Double = Double::new().min(0.01);
+
+pub struct Proxy {
+    internals: ModInternals,
+    host: String,
+    port: u16,
+    remote_module: String,
+    reconnect: f64,
+    timeout: Duration,
+    offline_status: StatusConst,
+    client: Option<RemoteClient>,
+    /// Description of the remote module, as fetched on the last connection.
+    description: Option<Value>,
+    /// Last known data reports of the remote parameters, for activation.
+    cache: Arc<Mutex<HashMap<String, Value>>>,
+    /// Why the remote module is currently not available.
+    offline_reason: String,
+    last_attempt: f64,
+}
+
+impl Module for Proxy {
+    fn create(internals: ModInternals) -> Result<Self> {
+        let params = &internals.config().parameters;
+        let uri = params.get("uri").and_then(Value::as_str).ok_or_else(
+            || Error::config("invalid or missing uri parameter"))?;
+        let (host, port) = parse_uri(uri)?;
+        let remote_module = match params.get("module") {
+            None => internals.name().into(),
+            Some(v) => v.as_str().ok_or_else(|| Error::config("invalid module parameter"))?.into(),
+        };
+        let reconnect = match params.get("reconnect") {
+            None => 10.0,
+            Some(v) => RECONNECT_TYPE.from_json(v).map_err(
+                |e| e.amend("in reconnect parameter"))?,
+        };
+        let timeout = match params.get("timeout") {
+            None => DEFAULT_TIMEOUT,
+            Some(v) => Duration::from_secs_f64(TIMEOUT_TYPE.from_json(v).map_err(
+                |e| e.amend("in timeout parameter"))?),
+        };
+        let offline_status = match params.get("offline_status") {
+            None => StatusConst::Error,
+            Some(v) => StatusConstType.from_json(v).map_err(
+                |e| e.amend("in offline_status parameter"))?,
+        };
+        Ok(Proxy { internals, host, port, remote_module, reconnect, timeout, offline_status,
+                   client: None, description: None, cache: Default::default(),
+                   offline_reason: "not connected yet".into(), last_attempt: 0.0 })
+    }
+
+    fn setup(&mut self) -> Result<()> {
+        // The remote node being down is not fatal, we retry during polling.
+        if let Err(e) = self.connect() {
+            warn!("could not connect to remote module: {}", e);
+            self.offline_reason = e.to_string();
+        }
+        Ok(())
+    }
+
+    fn teardown(&mut self) {
+        // close the connection, which also stops the relay thread
+        self.client.take();
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+impl Proxy {
+    /// Try to connect to the remote node and import the module description.
+    fn connect(&mut self) -> Result<()> {
+        self.last_attempt = self.internals.clock().now();
+        let client = RemoteClient::new(&self.host, self.port, self.remote_module.clone(),
+                                       self.timeout)?;
+        let node = client.describe()?;
+        let description = node["modules"].get(&self.remote_module).cloned().ok_or_else(
+            || Error::config(format!("remote node has no module {}", self.remote_module)))?;
+        self.cache.lock().clear();
+        let relay = Relay {
+            name: self.name().into(),
+            remote_module: self.remote_module.clone(),
+            updates: client.updates().clone(),
+            rep_sender: self.internals().rep_sender().clone(),
+            cache: Arc::clone(&self.cache),
+            offline_status: self.offline_status,
+            clock: Arc::clone(self.internals.clock()),
+        };
+        thread::spawn(move || relay.run());
+        // the initial updates go through the relay into our cache
+        client.activate()?;
+        info!("connected to module {} on {}:{}", self.remote_module, self.host, self.port);
+        self.client = Some(client);
+        self.description = Some(description);
+        Ok(())
+    }
+
+    /// Drop the client if the connection has been lost in the meantime.
+    fn check_connection(&mut self) {
+        if self.client.as_ref().map_or(false, |c| !c.is_connected()) {
+            warn!("connection to remote node lost");
+            self.client = None;
+            self.offline_reason = "connection to remote node lost".into();
+        }
+    }
+
+    fn status_report(&self) -> Value {
+        let status = StatusType.to_json((self.offline_status, self.offline_reason.clone()))
+                               .unwrap_or(Value::Null);
+        json!([status, {"t": self.internals.clock().now()}])
+    }
+
+    fn offline_error(&self) -> Error {
+        Error::comm_failed(format!("remote module not available: {}", self.offline_reason))
+    }
+
+    fn send_description(&self) {
+        if self.config().visibility != Visibility::None {
+            let _ = self.internals().rep_sender().send(
+                (None, Msg::Describing { id: self.name().into(), structure: self.describe() }));
+        }
+    }
+}
+
+impl ModuleBase for Proxy {
+    fn internals(&self) -> &ModInternals { &self.internals }
+    fn internals_mut(&mut self) -> &mut ModInternals { &mut self.internals }
+
+    /// Only the configuration parameters are known statically; the
+    /// accessibles are imported from the remote node.
+    fn class_info() -> ClassInfo {
+        let param = |name, doc, datainfo, mandatory, default, check| ParamInfo {
+            name, doc, datainfo, readonly: true, swonly: true, mandatory, default,
+            module_ref: false, history: 0, config_enum: false, check
+        };
+        ClassInfo {
+            name: "Proxy",
+            doc: "Module of a remote SEC node, made available in this node.",
+            params: vec![
+                param("uri", "address of the remote node as [secop://]host[:port]",
+                      serde_json::to_value(&Str::new()).unwrap(), true, None,
+                      |v| Str::new().from_json(v).and_then(|uri| parse_uri(&uri)).map(|_| ())),
+                param("module", "name of the module on the remote node",
+                      serde_json::to_value(&Str::new()).unwrap(), false, Some("local name"),
+                      |v| Str::new().from_json(v).map(|_| ())),
+                param("reconnect", "seconds between reconnection attempts",
+                      serde_json::to_value(&RECONNECT_TYPE).unwrap(), false, Some("10"),
+                      |v| RECONNECT_TYPE.from_json(v).map(|_| ())),
+                param("timeout", "seconds to wait for replies from the remote node",
+                      serde_json::to_value(&TIMEOUT_TYPE).unwrap(), false, Some("2"),
+                      |v| TIMEOUT_TYPE.from_json(v).map(|_| ())),
+                param("offline_status", "status code while the remote module is not available",
+                      serde_json::to_value(&StatusConstType).unwrap(), false, Some("Error"),
+                      |v| StatusConstType.from_json(v).map(|_| ())),
+            ],
+            commands: vec![],
+        }
+    }
+
+    /// While the remote module has never been reachable, only the status
+    /// parameter is described.  The description, visibility, group and
+    /// properties from the config are merged into the remote description.
+    fn describe(&self) -> Value {
+        let config = self.config();
+        let mut descr = match &self.description {
+            Some(description) => description.clone(),
+            None => status_only_description(config),
+        };
+        descr["description"] = json!(config.description);
+        descr["visibility"] = json!(config.visibility);
+        if let Some(group) = &config.group {
+            descr["group"] = json!(group);
+        }
+        for (key, value) in &config.properties {
+            descr[key] = value.clone();
+        }
+        descr
+    }
+
+    fn read(&mut self, param: &str) -> Result<Value> {
+        self.check_connection();
+        match &self.client {
+            Some(client) => {
+                let data = client.read(param)?;
+                self.cache.lock().insert(param.into(), data.clone());
+                Ok(data)
+            }
+            None if param == "status" => Ok(self.status_report()),
+            None => Err(self.offline_error()),
+        }
+    }
+
+    fn change(&mut self, param: &str, value: Value) -> Result<Value> {
+        self.check_connection();
+        match &self.client {
+            Some(client) => {
+                let data = client.change(param, value)?;
+                self.cache.lock().insert(param.into(), data.clone());
+                Ok(data)
+            }
+            None => Err(self.offline_error()),
+        }
+    }
+
+    fn command(&mut self, cmd: &str, arg: Value) -> Result<Value> {
+        self.check_connection();
+        match &self.client {
+            Some(client) => client.command(cmd, arg),
+            None => Err(self.offline_error()),
+        }
+    }
+
+    fn init_params(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn activate_updates(&mut self) -> Vec<Msg> {
+        self.check_connection();
+        if self.client.is_none() {
+            return vec![Msg::Update { module: self.name().into(), param: "status".into(),
+                                      data: self.status_report() }];
+        }
+        self.cache.lock().iter().map(|(param, data)| {
+            Msg::Update { module: self.name().into(), param: param.clone(), data: data.clone() }
+        }).collect()
+    }
+
+    /// The remote node does its own polling, so we only need to watch the
+    /// connection here, at the module's poll interval.
+    fn polled_params() -> Vec<(&'static str, PollInterval)> {
+        vec![("status", PollInterval::default())]
+    }
+
+    fn is_busy(&self) -> bool {
+        false
+    }
+
+    fn poll(&mut self, _: &str) {
+        self.check_connection();
+        let since_attempt = self.internals.clock().now() - self.last_attempt;
+        if self.client.is_none() && since_attempt >= self.reconnect {
+            match self.connect() {
+                Ok(()) => self.send_description(),
+                Err(e) => {
+                    debug!("reconnect failed: {}", e);
+                    self.offline_reason = e.to_string();
+                }
+            }
+        }
+    }
+}
+
+/// Relays events of the remote module to our clients, under the local name.
+struct Relay {
+    name: String,
+    remote_module: String,
+    updates: RepReceiver,
+    rep_sender: ModRepSender,
+    cache: Arc<Mutex<HashMap<String, Value>>>,
+    offline_status: StatusConst,
+    clock: Arc<dyn Clock>,
+}
+
+impl Relay {
+    fn run(self) {
+        mlzlog::set_thread_prefix(format!("[{}] ", self.name));
+        for msg in self.updates.iter() {
+            if let Msg::Update { module, param, data } = msg {
+                if module != self.remote_module {
+                    continue;
+                }
+                self.cache.lock().insert(param.clone(), data.clone());
+                let _ = self.rep_sender.send(
+                    (None, Msg::Update { module: self.name.clone(), param, data }));
+            }
+        }
+        // The channel is closed when the connection is gone.
+        let status = StatusType.to_json((self.offline_status,
+                                         "connection to remote node lost".into()))
+                               .unwrap_or(Value::Null);
+        let _ = self.rep_sender.send(
+            (None, Msg::Update { module: self.name, param: "status".into(),
+                                 data: json!([status, {"t": self.clock.now()}]) }));
+    }
+}
+
+/// Split an URI of the form `[secop://]host[:port]` into host and port.
+fn parse_uri(uri: &str) -> Result<(String, u16)> {
+    let addr = uri.strip_prefix("secop://").unwrap_or(uri).trim_end_matches('/');
+    match addr.rsplit_once(':') {
+        None => Ok((addr.into(), DEFAULT_PORT)),
+        Some((host, port)) => port.parse().map(|port| (host.into(), port)).map_err(
+            |_| Error::config(format!("invalid port in uri {:?}", uri))),
+    }
+}
@@ -29,10 +29,12 @@ use serialport::{self, TTYPort};
 use secop_core::prelude::*;
 use secop_derive::ModuleBase;
 
-use crate::support::comm::{CommClient, CommThread, HasComm};
+use crate::support::comm::{CommClient, CommThread, HasComm, impl_comm_handlers};
 
 
+/// Communicates with a device via a serial port.
 #[derive(ModuleBase)]
+#[property(name="interface_classes", value=r#"["Communicator"]"#)]
 #[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
 #[command(name="communicate", doc="communicate (write/read cycle)",
           argtype="Str(maxchars=1024)", restype="Str(maxchars=1024)")]
@@ -109,3 +111,5 @@ impl HasComm for SerialComm {
         self.comm.as_ref().ok_or_else(|| Error::comm_failed("connection not open"))
     }
 }
+
+impl_comm_handlers!(SerialCommHandlers for SerialComm);
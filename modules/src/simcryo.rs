@@ -25,10 +25,8 @@
 
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
 use log::*;
 use parking_lot::Mutex;
-use mlzutil::time::localtime;
 
 use secop_core::prelude::*;
 use secop_derive::*;
@@ -55,10 +53,11 @@ struct StateVars {
 
 struct CryoSimulator {
     vars: Arc<Mutex<StateVars>>,
+    /// The module's clock, so that the simulation can run faster than real time.
+    clock: Arc<dyn Clock>,
 }
 
 fn clamp(v: f64, min: f64, max: f64) -> f64 { v.min(max.max(min)).max(min.min(max)) }
-fn sleep_ms(v: u64) { thread::sleep(Duration::from_millis(v)) }
 
 /// Ported from the NICOS simulator, for comments see nicos/devices/generic/virtual.py
 impl CryoSimulator {
@@ -68,7 +67,7 @@ impl CryoSimulator {
 
         mlzlog::set_thread_prefix("[CryoSim] ");
 
-        let mut last_t = localtime();
+        let mut last_t = self.clock.now();
         let mut last_control = false;
         let mut damper = 1.0;
         let mut lastflow = 0.0;
@@ -78,11 +77,11 @@ impl CryoSimulator {
         let mut last_heaters = (0.0, 0.0);
 
         loop {
-            let t = localtime();
+            let t = self.clock.now();
             let h = t - last_t;
 
             if h < LOOPDELAY/damper {
-                sleep_ms((1000. * clamp(LOOPDELAY/damper - h, 0.1, 60.)) as u64);
+                self.clock.sleep(clamp(LOOPDELAY/damper - h, 0.1, 60.));
                 continue;
             }
 
@@ -204,8 +203,9 @@ impl CryoSimulator {
     }
 }
 
+/// Control mode of the simulated cryostat.
 #[derive(TypeInfo, Clone, PartialEq)]
-enum Mode {
+pub enum Mode {
     PID,
     OpenLoop,
 }
@@ -214,8 +214,9 @@ impl Default for Mode {
     fn default() -> Self { Mode::PID }
 }
 
+/// PID parameters of the simulated cryostat.
 #[derive(TypeInfo, Clone, PartialEq, Default)]
-struct PID {
+pub struct PID {
     #[datainfo="Double(min=0.0)"]
     p: Option<f64>,
     #[datainfo="Double(min=0.0)"]
@@ -224,48 +225,7 @@ struct PID {
     d: Option<f64>,
 }
 
-#[derive(ModuleBase)]
-#[param(name="status", doc="status",
-        datainfo="StatusType",
-        readonly=true)]
-#[param(name="value", doc="regulation temperature",
-        datainfo="Double(min=0.0)",
-        readonly=true, unit="K")]
-#[param(name="sample", doc="sample temperature",
-        datainfo="Double(min=0.0)",
-        readonly=true, unit="K")]
-#[param(name="target", doc="target temperature",
-        datainfo="Double(min=0.0)",
-        readonly=false, default="0.0", unit="K")]
-#[param(name="setpoint", doc="current setpoint for the temperature",
-        datainfo="Double(min=0.0)",
-        readonly=true, unit="K")]
-#[param(name="ramp", doc="setpoint ramping speed",
-        datainfo="Double(min=0.0, max=1e3)",
-        readonly=false, default="1.0", unit="K/min")]
-#[param(name="heater", doc="current heater setting",
-        datainfo="Double(min=0.0, max=100.0)",
-        readonly=true, unit="%")]
-#[param(name="p", doc="regulation coefficient P",
-        datainfo="Double(min=0.0)", polling="-5",
-        readonly=false, default="40.0", unit="%/K", group="pid")]
-#[param(name="i", doc="regulation coefficient I",
-        datainfo="Double(min=0.0, max=100.0)", polling="-5",
-        readonly=false, default="10.0", group="pid")]
-#[param(name="d", doc="regulation coefficient D",
-        datainfo="Double(min=0.0, max=100.0)", polling="-5",
-        readonly=false, default="2.0", group="pid")]
-#[param(name="pid", doc="regulation coefficients",
-        datainfo="PIDType", polling="0",
-        readonly=false, group="pid")]
-#[param(name="mode", doc="regulation mode",
-        datainfo="ModeType", polling="0",
-        readonly=false, default="Mode::PID", group="pid")]
-#[param(name="pollinterval", doc="polling interval",
-        datainfo="Double(min=1.0)", swonly=true,
-        readonly=false, default="1.0")]
-#[command(name="stop", doc="stop ramping the setpoint",
-          argtype="Null", restype="Null")]
+/// Simulated cryostat with a PID-controlled heater.
 pub struct SimCryo {
     internals: ModInternals,
     params: SimCryoParams,
@@ -284,7 +244,8 @@ impl Module for SimCryo {
     }
 
     fn setup(&mut self) -> Result<()> {
-        let sim = CryoSimulator { vars: Arc::clone(&self.vars) };
+        let sim = CryoSimulator { vars: Arc::clone(&self.vars),
+                                  clock: Arc::clone(self.internals.clock()) };
         thread::spawn(move || sim.run());
         Ok(())
     }
@@ -294,52 +255,95 @@ impl Module for SimCryo {
     }
 }
 
-impl SimCryo {
-    fn read_value(&mut self)    -> Result<f64> { Ok(self.vars.lock().regulation) }
-    fn read_sample(&mut self)   -> Result<f64> { Ok(self.vars.lock().sample) }
-    fn read_target(&mut self)   -> Result<f64> { Ok(self.vars.lock().target) }
-    fn read_setpoint(&mut self) -> Result<f64> { Ok(self.vars.lock().setpoint) }
-    fn read_ramp(&mut self)     -> Result<f64> { Ok(self.vars.lock().ramp) }
-    fn read_heater(&mut self)   -> Result<f64> { Ok(self.vars.lock().heater) }
-    fn read_p(&mut self)        -> Result<f64> { Ok(self.vars.lock().k_p) }
-    fn read_i(&mut self)        -> Result<f64> { Ok(self.vars.lock().k_i) }
-    fn read_d(&mut self)        -> Result<f64> { Ok(self.vars.lock().k_d) }
-    fn read_pid(&mut self)      -> Result<PID> {
-        let v = self.vars.lock();
-        Ok(PID { p: Some(v.k_p), i: Some(v.k_i), d: Some(v.k_d) })
-    }
-    fn read_mode(&mut self)     -> Result<Mode> {
-        Ok(if self.vars.lock().control { Mode::PID } else { Mode::OpenLoop })
-    }
-    fn read_status(&mut self)   -> Result<Status> {
+/// Simulated cryostat with a PID-controlled heater.
+#[secop::module]
+#[secop::stabilizer]
+#[secop::param(name="pollinterval", doc="polling interval",
+               datainfo="Double(min=1.0)", swonly=true,
+               readonly=false, default="1.0")]
+#[secop::param(name="tolerance", doc="tolerance for reaching the target",
+               datainfo="Double(min=0.0)", swonly=true,
+               readonly=false, default="0.1", unit="K", group="stability")]
+#[secop::param(name="settle_time", doc="time the value must stay within tolerance",
+               datainfo="Double(min=0.0)", swonly=true,
+               readonly=false, default="30.0", unit="s", group="stability")]
+#[secop::param(name="timeout", doc="time after which reaching the target is given up (0 = never)",
+               datainfo="Double(min=0.0)", swonly=true,
+               readonly=false, default="0.0", unit="s", group="stability")]
+impl SimCryoHandlers for SimCryo {
+    /// status
+    #[secop::param(datainfo="StatusType")]
+    fn read_status(&mut self) -> Result<Status> {
         Ok(if self.vars.lock().ramping {
             (StatusConst::Busy, "ramping".into())
         } else {
-            (StatusConst::Idle, "idle".into())
+            self.internals.stabilizer().status()
         })
     }
 
+    /// regulation temperature
+    #[secop::param(datainfo="Double(min=0.0)", unit="K", history="600")]
+    fn read_value(&mut self) -> Result<f64> { Ok(self.vars.lock().regulation) }
+
+    /// sample temperature
+    #[secop::param(datainfo="Double(min=0.0)", unit="K")]
+    fn read_sample(&mut self) -> Result<f64> { Ok(self.vars.lock().sample) }
+
+    /// target temperature
+    #[secop::param(datainfo="Double(min=0.0)", default="0.0", unit="K")]
+    fn read_target(&mut self) -> Result<f64> { Ok(self.vars.lock().target) }
     fn write_target(&mut self, value: f64) -> Result<()> { Ok(self.vars.lock().target = value) }
-    fn write_ramp(&mut self, value: f64)   -> Result<()> { Ok(self.vars.lock().ramp = value) }
-    fn write_p(&mut self, value: f64)      -> Result<()> {
+
+    /// current setpoint for the temperature
+    #[secop::param(datainfo="Double(min=0.0)", unit="K")]
+    fn read_setpoint(&mut self) -> Result<f64> { Ok(self.vars.lock().setpoint) }
+
+    /// setpoint ramping speed
+    #[secop::param(datainfo="Double(min=0.0, max=1e3)", default="1.0", unit="K/min",
+                   persistent=true)]
+    fn read_ramp(&mut self) -> Result<f64> { Ok(self.vars.lock().ramp) }
+    fn write_ramp(&mut self, value: f64) -> Result<()> { Ok(self.vars.lock().ramp = value) }
+
+    /// current heater setting
+    #[secop::param(datainfo="Double(min=0.0, max=100.0)", unit="%")]
+    fn read_heater(&mut self) -> Result<f64> { Ok(self.vars.lock().heater) }
+
+    /// regulation coefficient P
+    #[secop::param(datainfo="Double(min=0.0)", poll="5.0", busy_poll="0",
+                   default="40.0", unit="%/K", group="pid")]
+    fn read_p(&mut self) -> Result<f64> { Ok(self.vars.lock().k_p) }
+    fn write_p(&mut self, value: f64) -> Result<()> {
         self.vars.lock().k_p = value;
         let _ = self.read("pid");
         Ok(())
     }
-    fn write_i(&mut self, value: f64)      -> Result<()> {
+
+    /// regulation coefficient I
+    #[secop::param(datainfo="Double(min=0.0, max=100.0)", poll="5.0", busy_poll="0",
+                   default="10.0", group="pid")]
+    fn read_i(&mut self) -> Result<f64> { Ok(self.vars.lock().k_i) }
+    fn write_i(&mut self, value: f64) -> Result<()> {
         self.vars.lock().k_i = value;
         let _ = self.read("pid");
         Ok(())
     }
-    fn write_d(&mut self, value: f64)      -> Result<()> {
+
+    /// regulation coefficient D
+    #[secop::param(datainfo="Double(min=0.0, max=100.0)", poll="5.0", busy_poll="0",
+                   default="2.0", group="pid")]
+    fn read_d(&mut self) -> Result<f64> { Ok(self.vars.lock().k_d) }
+    fn write_d(&mut self, value: f64) -> Result<()> {
         self.vars.lock().k_d = value;
         let _ = self.read("pid");
         Ok(())
     }
-    fn write_mode(&mut self, value: Mode)  -> Result<()> {
-        Ok(self.vars.lock().control = value == Mode::PID)
-    }
 
+    /// regulation coefficients
+    #[secop::param(datainfo="PIDType", poll="0", group="pid")]
+    fn read_pid(&mut self) -> Result<PID> {
+        let v = self.vars.lock();
+        Ok(PID { p: Some(v.k_p), i: Some(v.k_i), d: Some(v.k_d) })
+    }
     fn write_pid(&mut self, value: PID) -> Result<()> {
         {
             let mut v = self.vars.lock();
@@ -353,9 +357,21 @@ impl SimCryo {
         Ok(())
     }
 
-    fn do_stop(&self, _: ()) -> Result<()> {
+    /// regulation mode
+    #[secop::param(datainfo="ModeType", poll="0", default="Mode::PID", group="pid")]
+    fn read_mode(&mut self) -> Result<Mode> {
+        Ok(if self.vars.lock().control { Mode::PID } else { Mode::OpenLoop })
+    }
+    fn write_mode(&mut self, value: Mode) -> Result<()> {
+        Ok(self.vars.lock().control = value == Mode::PID)
+    }
+
+    /// stop ramping the setpoint
+    #[secop::command(argtype="Null", restype="Null")]
+    fn do_stop(&mut self, _: ()) -> Result<()> {
         let mut v = self.vars.lock();
         v.target = v.setpoint;
+        self.internals.stabilizer_mut().stop();
         Ok(())
     }
 }
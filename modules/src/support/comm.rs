@@ -241,3 +241,41 @@ pub trait HasComm {
         panic!("multi_communicate is not yet implemented");
     }
 }
+
+/// Implements the handler trait of a communicator module by forwarding to
+/// its `HasComm` implementation.
+macro_rules! impl_comm_handlers {
+    ($handlers:ident for $ty:ty) => {
+        impl $handlers for $ty {
+            fn read_status(&mut self) -> Result<Status> {
+                HasComm::read_status(self)
+            }
+
+            fn do_communicate(&mut self, arg: String) -> Result<String> {
+                HasComm::do_communicate(self, arg)
+            }
+
+            fn do_writeline(&mut self, arg: String) -> Result<()> {
+                HasComm::do_writeline(self, arg)
+            }
+
+            fn do_readline(&mut self, arg: ()) -> Result<String> {
+                HasComm::do_readline(self, arg)
+            }
+
+            fn do_write(&mut self, arg: String) -> Result<()> {
+                HasComm::do_write(self, arg)
+            }
+
+            fn do_read(&mut self, arg: ()) -> Result<String> {
+                HasComm::do_read(self, arg)
+            }
+
+            fn do_multi_communicate(&mut self, req: Vec<(String, f64)>) -> Result<Vec<String>> {
+                HasComm::do_multi_communicate(self, req)
+            }
+        }
+    }
+}
+
+pub(crate) use impl_comm_handlers;
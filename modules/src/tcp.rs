@@ -29,11 +29,13 @@ use log::*;
 use secop_core::prelude::*;
 use secop_derive::ModuleBase;
 
-use crate::support::comm::{CommClient, CommThread, HasComm};
+use crate::support::comm::{CommClient, CommThread, HasComm, impl_comm_handlers};
 
 
+/// Communicates with a device via a TCP connection.
 #[derive(ModuleBase)]
 // TODO: factor out these common params/commands
+#[property(name="interface_classes", value=r#"["Communicator"]"#)]
 #[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
 #[command(name="communicate", doc="communicate (write/read cycle)",
           argtype="Str(maxchars=1024)", restype="Str(maxchars=1024)")]
@@ -108,3 +110,5 @@ impl HasComm for TcpComm {
         self.comm.as_ref().ok_or_else(|| Error::comm_failed("connection not open"))
     }
 }
+
+impl_comm_handlers!(TcpCommHandlers for TcpComm);
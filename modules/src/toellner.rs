@@ -29,12 +29,13 @@ use secop_core::prelude::*;
 use secop_derive::ModuleBase;
 
 
+/// Controls one channel of a Toellner power supply.
 #[derive(ModuleBase)]
 #[param(name="status", doc="status", datainfo="StatusType", readonly=true)]
 #[param(name="value", doc="current value", datainfo="Double()", readonly=true)]
 #[param(name="target", doc="target value", datainfo="Double()", readonly=false)]
 #[param(name="iomod", doc="module name of port", datainfo="Str(maxchars=64)", readonly=true,
-        mandatory=true, swonly=true, visibility="none")]
+        mandatory=true, swonly=true, module_ref=true, visibility="none")]
 #[param(name="channel", doc="channel to control", datainfo="Int(min=1, max=2)", readonly=true,
         default="1", swonly=true, visibility="none")]
 pub struct ToellnerPS {
@@ -60,7 +61,7 @@ impl Module for ToellnerPS {
     fn teardown(&mut self) {}
 }
 
-impl ToellnerPS {
+impl ToellnerPSHandlers for ToellnerPS {
     fn read_value(&mut self) -> Result<f64> {
         let reply = self.io.command("communicate", json!("MV1?"))?;
         reply[0].as_str().and_then(|v| v.parse().ok()).ok_or_else(
@@ -75,7 +76,7 @@ impl ToellnerPS {
         Ok(0.0)
     }
 
-    fn write_target(&mut self, _tgt: f64) -> Result<f64> {
+    fn write_target(&mut self, _tgt: f64) -> Result<()> {
         Err(Error::bad_value("not implemented yet"))
     }
 }
@@ -0,0 +1,218 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for the proxy module, against a fake remote node in this process.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use crossbeam_channel::{bounded, unbounded};
+use parking_lot::Mutex;
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::proto::Msg;
+use secop_core::server::ModRepReceiver;
+use secop_core::state::StateStore;
+use secop_modules::proxy::Proxy;
+
+/// A remote node with a single module "remote", which has a readonly "value",
+/// a writable "target" and a "double" command.
+struct FakeNode {
+    port: u16,
+    target: Arc<Mutex<f64>>,
+    online: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl FakeNode {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let node = FakeNode {
+            port: listener.local_addr().unwrap().port(),
+            target: Arc::new(Mutex::new(1.5)),
+            online: Arc::new(AtomicBool::new(true)),
+            connections: Default::default(),
+        };
+        let (target, online, connections) = (node.target.clone(), node.online.clone(),
+                                             node.connections.clone());
+        thread::spawn(move || for conn in listener.incoming() {
+            let conn = conn.unwrap();
+            // while offline, connections are closed right away
+            if online.load(Ordering::SeqCst) {
+                connections.lock().push(conn.try_clone().unwrap());
+                let target = target.clone();
+                thread::spawn(move || FakeNode::handle(conn, target));
+            }
+        });
+        node
+    }
+
+    fn handle(mut conn: TcpStream, target: Arc<Mutex<f64>>) {
+        let report = |value: f64| json!([value, {"t": 1.0}]);
+        let update = |param: &str, data| Msg::Update {
+            module: "remote".into(), param: param.into(), data
+        };
+        let reader = BufReader::new(conn.try_clone().unwrap());
+        for line in reader.lines() {
+            let msg = match Msg::parse(line.unwrap()) {
+                Ok(msg) => msg.1,
+                Err(_) => break,
+            };
+            let target_value = *target.lock();
+            let replies = match msg {
+                Msg::Describe => vec![Msg::Describing { id: ".".into(), structure: json!({
+                    "modules": {"remote": {"accessibles": {
+                        "value": {"datainfo": {"type": "double"}, "readonly": true},
+                        "target": {"datainfo": {"type": "double"}, "readonly": false},
+                        "double": {"datainfo": {"type": "command"}},
+                    }}}
+                })}],
+                Msg::Activate { module } => vec![update("value", report(target_value)),
+                                                 Msg::Active { module }],
+                Msg::Read { param, .. } if param == "value" || param == "target" =>
+                    vec![update(&param, report(target_value))],
+                Msg::Change { module, param, value } if param == "target" => {
+                    *target.lock() = value.as_f64().unwrap();
+                    vec![update("value", report(value.as_f64().unwrap())),
+                         Msg::Changed { module, param, data: report(value.as_f64().unwrap()) }]
+                }
+                Msg::Do { module, command, arg } if command == "double" =>
+                    vec![Msg::Done { module, command, data: json!([arg.as_f64().unwrap() * 2.0,
+                                                                   {}]) }],
+                _ => vec![Msg::ErrMsg { class: "NoSuchParameter".into(),
+                                        report: json!(["", "no such parameter", {}]) }],
+            };
+            for reply in replies {
+                if writeln!(conn, "{}", reply).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Close all connections and refuse new ones.
+    fn go_offline(&self) {
+        self.online.store(false, Ordering::SeqCst);
+        for conn in self.connections.lock().drain(..) {
+            let _ = conn.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    fn go_online(&self) {
+        self.online.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Wait for the next update of the given parameter of the proxy.
+fn next_update(rep_receiver: &ModRepReceiver, param: &str) -> Value {
+    while let Ok((_, msg)) = rep_receiver.recv_timeout(Duration::from_secs(5)) {
+        if let Msg::Update { module, param: p, data } = msg {
+            assert_eq!(module, "proxy");
+            if p == param {
+                return data;
+            }
+        }
+    }
+    panic!("no update for {}", param);
+}
+
+#[test]
+fn forwarding_and_reconnect() {
+    let node = FakeNode::start();
+    let clock = Arc::new(VirtualClock::new(100.));
+    let config = serde_json::from_value(json!({
+        "class": "Proxy", "description": "test", "group": "remote",
+        "visibility": "expert", "parameters": {
+            "uri": format!("localhost:{}", node.port), "module": "remote",
+            "reconnect": 5.0, "timeout": 1.0,
+        }
+    })).unwrap();
+    let (_req_sender, req_receiver) = unbounded();
+    let (rep_sender, rep_receiver) = unbounded();
+    let internals = ModInternals::new("proxy".into(), config, req_receiver, rep_sender,
+                                      bounded(1).0, Arc::new(StateStore::in_memory()))
+        .with_clock(clock.clone());
+    let mut proxy = Proxy::create(internals).unwrap();
+    proxy.init_params().unwrap();
+    proxy.setup().unwrap();
+
+    // the description is imported, and updates are relayed under our name
+    let descr = proxy.describe();
+    assert!(descr["accessibles"]["double"].is_object());
+    // the local config overrides the remote description
+    assert_eq!(descr["description"], json!("test"));
+    assert_eq!(descr["visibility"], json!("expert"));
+    assert_eq!(descr["group"], json!("remote"));
+    assert_eq!(next_update(&rep_receiver, "value"), json!([1.5, {"t": 1.0}]));
+
+    // requests are forwarded
+    assert_eq!(proxy.read("value").unwrap(), json!([1.5, {"t": 1.0}]));
+    assert_eq!(proxy.change("target", json!(3.0)).unwrap(), json!([3.0, {"t": 1.0}]));
+    assert_eq!(next_update(&rep_receiver, "value"), json!([3.0, {"t": 1.0}]));
+    assert_eq!(proxy.read("target").unwrap()[0], json!(3.0));
+    assert_eq!(proxy.command("double", json!(2.5)).unwrap()[0], json!(5.0));
+    assert!(proxy.read("unknown").is_err());
+
+    // the connection is lost: the offline status is sent, and requests fail
+    node.go_offline();
+    let offline = json!([400, "connection to remote node lost"]);
+    assert_eq!(next_update(&rep_receiver, "status")[0], offline);
+    assert!(proxy.read("value").is_err());
+    assert_eq!(proxy.read("status").unwrap()[0], offline);
+    assert_eq!(proxy.activate_updates().len(), 1);
+
+    // no reconnect before the interval has passed
+    node.go_online();
+    proxy.poll("status");
+    assert!(proxy.read("value").is_err());
+    clock.advance(5.);
+    proxy.poll("status");
+    assert_eq!(proxy.read("value").unwrap()[0], json!(3.0));
+    assert_eq!(proxy.command("double", json!(1.0)).unwrap()[0], json!(2.0));
+}
+
+#[test]
+fn parameter_bounds() {
+    let info = Proxy::class_info();
+    let check = |param: &str, value: Value| {
+        let in_info = (info.param(param).unwrap().check)(&value).is_ok();
+        let config = serde_json::from_value(json!({
+            "class": "Proxy", "description": "test",
+            "parameters": {"uri": "localhost:1", param: value}
+        })).unwrap();
+        let internals = ModInternals::new("proxy".into(), config, unbounded().1, unbounded().0,
+                                          bounded(1).0, Arc::new(StateStore::in_memory()));
+        let in_create = Proxy::create(internals).is_ok();
+        assert_eq!(in_info, in_create, "{} = {}", param, value);
+        in_create
+    };
+    assert!(check("reconnect", json!(0.0)));
+    assert!(check("reconnect", json!(10)));
+    assert!(!check("reconnect", json!(-1.0)));
+    assert!(check("timeout", json!(0.5)));
+    assert!(!check("timeout", json!(0.0)));
+}
@@ -0,0 +1,75 @@
+// -----------------------------------------------------------------------------
+// Rust SECoP playground
+//
+// This program is free software; you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation; either version 2 of the License, or (at your option) any later
+// version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program; if not, write to the Free Software Foundation, Inc.,
+// 59 Temple Place, Suite 330, Boston, MA  02111-1307  USA
+//
+// Module authors:
+//   Georg Brandl <g.brandl@fz-juelich.de>
+//
+// -----------------------------------------------------------------------------
+//
+//! Tests for the simulated cryostat, running faster than real time.
+
+use std::sync::Arc;
+use crossbeam_channel::{bounded, unbounded};
+use serde_json::{Value, json};
+
+use secop_core::prelude::*;
+use secop_core::clock::VirtualClock;
+use secop_core::state::StateStore;
+use secop_modules::simcryo::SimCryo;
+
+fn value(cryo: &mut SimCryo, param: &str) -> Value {
+    cryo.read(param).unwrap()[0].clone()
+}
+
+#[test]
+fn warmup() {
+    // one simulated hour takes less than a second
+    let clock = Arc::new(VirtualClock::with_speed(0., 10000.));
+    let config = serde_json::from_value(json!({
+        "class": "SimCryo", "description": "test", "parameters": {"ramp": 10.0}
+    })).unwrap();
+    let (_req_sender, req_receiver) = unbounded();
+    let (rep_sender, _rep_receiver) = unbounded();
+    let internals = ModInternals::new("cryo".into(), config, req_receiver, rep_sender,
+                                      bounded(1).0, Arc::new(StateStore::in_memory()))
+        .with_clock(clock.clone());
+    let mut cryo = SimCryo::create(internals).unwrap();
+    cryo.init_params().unwrap();
+    cryo.setup().unwrap();
+
+    assert_eq!(value(&mut cryo, "status"), json!([100, "idle"]));
+    cryo.change("target", json!(50.0)).unwrap();
+
+    // poll the value and status every 10 seconds, recording the sequence
+    // of status messages
+    let mut statuses = vec![];
+    for _ in 0..360 {
+        clock.sleep(10.);
+        value(&mut cryo, "value");
+        let status = value(&mut cryo, "status")[1].as_str().unwrap().to_owned();
+        if statuses.last() != Some(&status) {
+            statuses.push(status);
+        }
+    }
+    // overshooting can take the value out of tolerance again
+    assert_eq!(statuses.first().unwrap(), "ramping");
+    assert!(statuses.iter().any(|s| s == "stabilizing"));
+    assert_eq!(statuses.last().unwrap(), "target reached");
+    assert_eq!(value(&mut cryo, "setpoint"), json!(50.0));
+    let temp = value(&mut cryo, "value").as_f64().unwrap();
+    assert!((temp - 50.0).abs() < 0.1, "temperature is {}", temp);
+}
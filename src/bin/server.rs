@@ -27,6 +27,9 @@ use mlzutil::fs as fsutil;
 use clap::Parser;
 
 use secop_core::config;
+use secop_core::module::ClassInfo;
+use secop_core::registry::Registry;
+use secop_core::proto::DISCOVERY_PORT;
 use secop_core::server::Server;
 
 
@@ -38,14 +41,91 @@ struct Options {
     log: Option<String>,
     #[clap(long="bind", help="Bind address (host:port)", default_value="0.0.0.0:10767")]
     bind: String,
-    #[clap(help="Configuration file name to load")]
-    config: String,
+    #[clap(long="no-discovery", help="Do not answer UDP discovery requests")]
+    no_discovery: bool,
+    #[clap(long="announce", help="Announce the node by UDP broadcast on startup")]
+    announce: bool,
+    #[clap(long="check", help="Only check the configuration file and exit")]
+    check: bool,
+    #[clap(long="list-classes", help="List the available module classes and exit")]
+    list_classes: bool,
+    #[clap(long="describe-class", help="Describe the given module class and exit")]
+    describe_class: Option<String>,
+    #[clap(help="Configuration file name to load",
+           required_unless_present_any=&["list-classes", "describe-class"])]
+    config: Option<String>,
+}
+
+
+/// Print parameters and commands of a module class in human readable form.
+fn print_class(class: &str, info: &ClassInfo) {
+    println!("{} - {}", class, info.doc);
+    println!();
+    println!("Parameters:");
+    for p in &info.params {
+        let mut flags = vec![if p.readonly { "readonly" } else { "writable" }];
+        if p.swonly { flags.push("software-only"); }
+        if p.mandatory { flags.push("mandatory"); }
+        if p.module_ref { flags.push("module reference"); }
+        match p.default {
+            Some(def) => println!("  {} ({}, default: {})", p.name, flags.join(", "), def),
+            None => println!("  {} ({})", p.name, flags.join(", ")),
+        }
+        println!("      {}", p.doc);
+        println!("      datainfo: {}", p.datainfo);
+    }
+    if !info.commands.is_empty() {
+        println!();
+        println!("Commands:");
+        for c in &info.commands {
+            println!("  {}", c.name);
+            println!("      {}", c.doc);
+            println!("      argument: {}", c.argument);
+            println!("      result: {}", c.result);
+        }
+    }
 }
 
 
 fn main() {
     let opts = Options::from_args();
 
+    // collect the module classes; crates with additional modules can register
+    // their classes here as well
+    let mut registry = Registry::new();
+    secop_modules::register(&mut registry);
+
+    // informational and checking modes don't need any setup; if a config is
+    // given, classes from its plugins are included
+    if opts.check || opts.list_classes || opts.describe_class.is_some() {
+        if let Some(config_file) = &opts.config {
+            if let Err(err) = config::load_config(config_file, &mut registry) {
+                eprintln!("{}: {}", config_file, err);
+                std::process::exit(1);
+            }
+            if opts.check {
+                println!("{}: config is valid", config_file);
+            }
+        }
+        if opts.list_classes {
+            for class in registry.class_names() {
+                let doc = registry.class_info(class).map(|info| info.doc).unwrap_or_default();
+                println!("{:<12} {}", class, doc.lines().next().unwrap_or_default());
+            }
+        }
+        if let Some(class) = &opts.describe_class {
+            match registry.class_info(class) {
+                Some(info) => print_class(class, &info),
+                None => {
+                    eprintln!("no such module class: {}", class);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+    let config_file = opts.config.as_deref().expect("config file is required");
+
     let log_path = opts.log.as_ref().map(|l| fsutil::abspath(l));
     let log_console = log_path.is_none();
 
@@ -83,12 +163,13 @@ fn main() {
     }));
 
     // load the config and run!
-    match config::load_config(&opts.config) {
-        Err(err) => error!("could not parse config file {}: {}", opts.config, err),
+    match config::load_config(config_file, &mut registry) {
+        Err(err) => error!("could not parse config file {}: {}", config_file, err),
         Ok(cfg)  => {
-            let server = Server::new(cfg);
+            let discovery_port = if opts.no_discovery { None } else { Some(DISCOVERY_PORT) };
+            let server = Server::new(cfg).discovery(discovery_port, opts.announce);
             info!("starting server on {}...", opts.bind);
-            if let Err(err) = server.start(&opts.bind, secop_modules::run_module) {
+            if let Err(err) = server.start(&opts.bind, |internals| registry.run_module(internals)) {
                 error!("could not initialize server: {}", err);
             } else {
                 // server is running; wait for a signal to finish